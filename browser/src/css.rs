@@ -1,10 +1,18 @@
 //! A simple parser for a tiny subset of CSS.
 //!
+//! As CSS error handling requires, a rule whose selectors are unsupported or
+//! malformed is dropped whole, and a malformed declaration is dropped alone,
+//! each with a warning (see `parse_with_warnings`). A declaration whose value
+//! doesn't suit its property is kept, to be ignored when styling.
+//!
 //! To support more CSS syntax, it would probably be easiest to replace this
-//! hand-rolled parser with one based on a library or parser generator.
+//! hand-rolled parser with one based on a library or parser generator, as the
+//! `library_css` module does with `cssparser`.
 
 use crate::utility::Color;
 use crate::user_agent;
+use itertools::Itertools;
+use std::fmt;
 
 // Data structures:
 
@@ -22,19 +30,90 @@ pub struct Rule {
 #[derive(Clone, Debug)]
 pub enum Selector {
     Simple(SimpleSelector),
+    /// Simple selectors joined by combinators, e.g.: `ul > li a`. The first
+    /// simple selector is the subject (i.e., rightmost), and each combinator
+    /// is paired with the simple selector to its left.
+    Compound(SimpleSelector, Vec<(Combinator, SimpleSelector)>),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Combinator {
+    /// Whitespace, e.g.: `div p`
+    Descendant,
+    /// `>`, e.g.: `ul > li`
+    Child,
+    /// `+`, e.g.: `h1 + p`
+    NextSibling,
+    /// `~`, e.g.: `h1 ~ p`
+    SubsequentSibling,
+}
+
+#[derive(Clone, Default, Debug)]
 pub struct SimpleSelector {
     pub tag: Option<String>,
     pub id: Option<String>,
     pub class: Vec<String>,
+    pub attribute: Vec<AttributeSelector>,
+    pub pseudo_class: Vec<PseudoClass>,
+    pub pseudo_element: Option<PseudoElement>,
+}
+
+/// A pseudo-element for generated content, e.g.: `::before`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PseudoElement {
+    Before,
+    After,
+}
+
+/// An attribute selector, e.g.: `[lang|=en]`
+#[derive(Clone, Debug)]
+pub struct AttributeSelector {
+    pub name: String,
+    pub operation: AttributeOperation,
+}
+
+/// A structural pseudo-class, e.g.: `:first-child`
+#[derive(Clone, Debug)]
+pub enum PseudoClass {
+    FirstChild,
+    LastChild,
+    OnlyChild,
+    /// `:nth-child(an+b)`, as `(a, b)`
+    NthChild(i32, i32),
+    /// `:nth-of-type(an+b)`, as `(a, b)`
+    NthOfType(i32, i32),
+    Empty,
+    Root,
+    /// `:not(<simple selector>)`
+    Not(Box<SimpleSelector>),
+}
+
+/// The test applied to an attribute's value by an attribute selector.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum AttributeOperation {
+    /// `[attr]`
+    Exists,
+    /// `[attr=value]`
+    Equals(String),
+    /// `[attr~=value]`, for a whitespace-separated list containing `value`
+    Includes(String),
+    /// `[attr|=value]`, for `value` exactly or followed by `-`
+    DashMatch(String),
+    /// `[attr^=value]`
+    Prefix(String),
+    /// `[attr$=value]`
+    Suffix(String),
+    /// `[attr*=value]`
+    Substring(String),
 }
 
 #[derive(Clone, Debug)]
 pub struct Declaration {
     pub name: String,
-    pub value: Value,
+    /// The space-separated component values.
+    pub value: Vec<Value>,
+    /// Is this declaration marked `!important`?
+    pub important: bool,
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -42,7 +121,12 @@ pub enum Value {
     Keyword(String),
     Length(f32, Unit),
     Percent(f32),
+    /// A unitless number, e.g.: `2`
+    Number(f32),
     ColorValue(Color),
+    StringValue(String),
+    /// A functional notation, e.g.: `attr(title)`
+    Function(String, Vec<Value>),
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -54,22 +138,55 @@ pub enum Unit {
     Pc,
     Pt,
     Px,
-    // Em,
-    // Rem,
+    // relative to the font size
+    Em,
+    Rem,
+    Ex,
+    Ch,
+    // relative to the viewport size
+    Vw,
+    Vh,
+    Vmin,
+    Vmax,
 }
 
 impl Unit {
-    pub fn to_px(self, length: f32) -> f32 {
+    /// Lookup a unit by its (ASCII case-insensitive) name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match &*name.to_ascii_lowercase() {
+            "cm" => Some(Unit::Cm),
+            "mm" => Some(Unit::Mm),
+            "q" => Some(Unit::Q),
+            "in" => Some(Unit::In),
+            "pc" => Some(Unit::Pc),
+            "pt" => Some(Unit::Pt),
+            "px" => Some(Unit::Px),
+            "em" => Some(Unit::Em),
+            "rem" => Some(Unit::Rem),
+            "ex" => Some(Unit::Ex),
+            "ch" => Some(Unit::Ch),
+            "vw" => Some(Unit::Vw),
+            "vh" => Some(Unit::Vh),
+            "vmin" => Some(Unit::Vmin),
+            "vmax" => Some(Unit::Vmax),
+            _ => None,
+        }
+    }
+
+    /// Convert a length in this unit to pixels, unless the unit is relative
+    /// (in which case the length must be resolved in context).
+    pub fn to_px(self, length: f32) -> Option<f32> {
         use Unit::*;
 
         match self {
-            Cm => In.to_px(length) / 2.54,
-            Mm => Cm.to_px(length) / 10.0,
-            Q => Cm.to_px(length) / 40.0,
-            In => length * 96.0,
-            Pc => In.to_px(length) / 6.0,
-            Pt => In.to_px(length) / 72.0,
-            Px => length,
+            Cm => In.to_px(length).map(|px| px / 2.54),
+            Mm => Cm.to_px(length).map(|px| px / 10.0),
+            Q => Cm.to_px(length).map(|px| px / 40.0),
+            In => Some(length * 96.0),
+            Pc => In.to_px(length).map(|px| px / 6.0),
+            Pt => In.to_px(length).map(|px| px / 72.0),
+            Px => Some(length),
+            Em | Rem | Ex | Ch | Vw | Vh | Vmin | Vmax => None,
         }
     }
 }
@@ -77,13 +194,82 @@ impl Unit {
 pub type Specificity = (usize, usize, usize);
 
 impl Selector {
+    /// Assemble a selector from its simple selectors and the combinators
+    /// between them, from left to right.
+    pub fn from_sequence(
+        mut simple_selectors: Vec<SimpleSelector>,
+        combinators: Vec<Combinator>,
+    ) -> Self {
+        assert_eq!(simple_selectors.len(), combinators.len() + 1);
+        // Pair each combinator with the simple selector to its left, from
+        // right to left.
+        let subject = simple_selectors.pop().unwrap();
+        if combinators.is_empty() {
+            return Selector::Simple(subject);
+        }
+        let context = combinators.into_iter().rev().zip(simple_selectors.into_iter().rev());
+        Selector::Compound(subject, context.collect())
+    }
+
+    /// The pseudo-element this selector's subject is, if any.
+    pub fn pseudo_element(&self) -> Option<PseudoElement> {
+        match *self {
+            Selector::Simple(ref subject) | Selector::Compound(ref subject, _) => {
+                subject.pseudo_element
+            }
+        }
+    }
+
     pub fn specificity(&self) -> Specificity {
         // http://www.w3.org/TR/selectors/#specificity
-        let Selector::Simple(ref simple) = *self;
-        let a = simple.id.iter().count();
-        let b = simple.class.len();
-        let c = simple.tag.iter().count();
-        (a, b, c)
+        match *self {
+            Selector::Simple(ref simple) => simple.specificity(),
+            Selector::Compound(ref subject, ref context) => context
+                .iter()
+                .map(|(_, simple)| simple.specificity())
+                .fold(subject.specificity(), |(a, b, c), (x, y, z)| (a + x, b + y, c + z)),
+        }
+    }
+}
+
+impl PseudoClass {
+    /// Lookup a pseudo-class without arguments by its (lowercased) name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "first-child" => Some(PseudoClass::FirstChild),
+            "last-child" => Some(PseudoClass::LastChild),
+            "only-child" => Some(PseudoClass::OnlyChild),
+            "empty" => Some(PseudoClass::Empty),
+            "root" => Some(PseudoClass::Root),
+            _ => None,
+        }
+    }
+}
+
+impl PseudoElement {
+    /// Lookup a pseudo-element by its (lowercased) name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "before" => Some(PseudoElement::Before),
+            "after" => Some(PseudoElement::After),
+            _ => None,
+        }
+    }
+}
+
+impl SimpleSelector {
+    pub fn specificity(&self) -> Specificity {
+        let a = self.id.iter().count();
+        let b = self.class.len() + self.attribute.len();
+        let c = self.tag.iter().count() + self.pseudo_element.iter().count();
+        // A negation counts only its argument.
+        self.pseudo_class.iter().fold((a, b, c), |(a, b, c), pseudo_class| match *pseudo_class {
+            PseudoClass::Not(ref argument) => {
+                let (x, y, z) = argument.specificity();
+                (a + x, b + y, c + z)
+            }
+            _ => (a, b + 1, c),
+        })
     }
 }
 
@@ -93,7 +279,10 @@ impl std::fmt::Display for Value {
             Value::Keyword(ref kw) => f.write_str(kw),
             Value::Length(l, u) => write!(f, "{}{}", l, u),
             Value::Percent(p) => write!(f, "{}%", p),
+            Value::Number(n) => write!(f, "{}", n),
             Value::ColorValue(c) => write!(f, "{}", c),
+            Value::StringValue(s) => write!(f, "{:?}", s),
+            Value::Function(name, args) => write!(f, "{}({})", name, args.iter().join(", ")),
         }
     }
 }
@@ -108,14 +297,51 @@ impl std::fmt::Display for Unit {
             Unit::Pc => f.write_str("pc"),
             Unit::Pt => f.write_str("pt"),
             Unit::Px => f.write_str("px"),
-            // Unit::Em => f.write_str("em"),
+            Unit::Em => f.write_str("em"),
+            Unit::Rem => f.write_str("rem"),
+            Unit::Ex => f.write_str("ex"),
+            Unit::Ch => f.write_str("ch"),
+            Unit::Vw => f.write_str("vw"),
+            Unit::Vh => f.write_str("vh"),
+            Unit::Vmin => f.write_str("vmin"),
+            Unit::Vmax => f.write_str("vmax"),
         }
     }
 }
 
+/// A dropped rule or declaration, positioned by line and column (from 1).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Warning {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
 /// Parse a CSS stylesheet.
 pub fn parse(source: String) -> Stylesheet {
-    Stylesheet { rules: Parser::new(source).parse_rules() }
+    parse_with_warnings(source).0
+}
+
+/// Parse a CSS stylesheet like `parse`, also returning a warning for each
+/// rule or declaration that was dropped, in source order.
+pub fn parse_with_warnings(source: String) -> (Stylesheet, Vec<Warning>) {
+    let mut parser = Parser::new(source);
+    let rules = parser.parse_rules();
+    (Stylesheet { rules }, parser.warnings)
+}
+
+/// Parse a list of declarations, e.g.: the value of a `style` attribute,
+/// also returning a warning for each declaration that was dropped.
+pub fn parse_declarations(source: String) -> (Vec<Declaration>, Vec<Warning>) {
+    let mut parser = Parser::new(source);
+    let declarations = parser.parse_declaration_list();
+    (declarations, parser.warnings)
 }
 
 /// Parse the user agent stylesheet.
@@ -126,12 +352,13 @@ pub fn user_agent() -> Stylesheet {
 struct Parser {
     pos: usize,
     input: String,
+    warnings: Vec<Warning>,
 }
 
 impl Parser {
     /// Assemble initial parser state for an owned string of input.
     fn new(input: String) -> Self {
-        Parser { pos: 0, input }
+        Parser { pos: 0, input, warnings: Vec::new() }
     }
 
     /// Parse a list of rule sets, separated by optional whitespace.
@@ -142,46 +369,92 @@ impl Parser {
             if self.eof() {
                 break;
             }
-            rules.push(self.parse_rule());
+            let start = self.pos;
+            match self.parse_rule() {
+                Ok(rule) => rules.push(rule),
+                // As in CSS, a rule with an invalid selector is dropped whole.
+                Err(reason) => {
+                    let error_pos = self.pos;
+                    self.skip_rule();
+                    let source = self.input[start..self.pos].trim().to_string();
+                    self.warn(error_pos, format!("{}; dropped `{}`", reason, source));
+                }
+            }
         }
         rules
     }
 
     /// Parse a rule set: `<selectors> { <declarations> }`.
-    fn parse_rule(&mut self) -> Rule {
-        Rule {
-            selectors: self.parse_selectors(),
+    fn parse_rule(&mut self) -> Result<Rule, String> {
+        Ok(Rule {
+            selectors: self.parse_selectors()?,
             declarations: self.parse_declarations(),
+        })
+    }
+
+    /// Skip the rest of an invalid rule, up to the end of its `{ ... }` block.
+    fn skip_rule(&mut self) {
+        let mut depth = 0;
+        while !self.eof() {
+            match self.consume_char() {
+                '{' => depth += 1,
+                '}' if depth == 1 => break,
+                '}' if depth > 1 => depth -= 1,
+                _ => {}
+            }
         }
     }
 
     /// Parse a comma-separated list of selectors.
-    fn parse_selectors(&mut self) -> Vec<Selector> {
+    fn parse_selectors(&mut self) -> Result<Vec<Selector>, String> {
         let mut selectors = Vec::new();
         loop {
-            selectors.push(Selector::Simple(self.parse_simple_selector()));
-            self.advance();
-            match self.next_char() {
-                ',' => {
+            selectors.push(self.parse_selector()?);
+            match self.peek().chars().next() {
+                Some(',') => {
                     self.consume_char();
                     self.advance();
                 }
-                '{' => break,
-                c => panic!("Unexpected character {} in selector list", c),
+                Some('{') => break,
+                _ => return Err(self.unexpected("selector list")),
             }
         }
         // Return selectors with highest specificity first, for use in matching.
-        selectors.sort_by(|a, b| b.specificity().cmp(&a.specificity()));
-        selectors
+        selectors.sort_by_key(|selector| std::cmp::Reverse(selector.specificity()));
+        Ok(selectors)
+    }
+
+    /// Parse one selector, i.e., simple selectors joined by combinators, up to
+    /// the next `,` or `{`.
+    fn parse_selector(&mut self) -> Result<Selector, String> {
+        let mut simple_selectors = vec![self.parse_simple_selector()?];
+        let mut combinators = Vec::new();
+        loop {
+            let start = self.pos;
+            self.advance();
+            let combinator = match self.peek().chars().next() {
+                Some(',') | Some('{') => break,
+                Some('>') => Combinator::Child,
+                Some('+') => Combinator::NextSibling,
+                Some('~') => Combinator::SubsequentSibling,
+                Some(_) if self.pos > start => Combinator::Descendant,
+                _ => return Err(self.unexpected("selector")),
+            };
+            if combinator != Combinator::Descendant {
+                self.consume_char();
+                self.advance();
+            }
+            combinators.push(combinator);
+            simple_selectors.push(self.parse_simple_selector()?);
+        }
+
+        Ok(Selector::from_sequence(simple_selectors, combinators))
     }
 
     /// Parse one simple selector, e.g.: `type#id.class1.class2.class3`
-    fn parse_simple_selector(&mut self) -> SimpleSelector {
-        let mut selector = SimpleSelector {
-            tag: None,
-            id: None,
-            class: Vec::new(),
-        };
+    fn parse_simple_selector(&mut self) -> Result<SimpleSelector, String> {
+        let start = self.pos;
+        let mut selector = SimpleSelector::default();
         while !self.eof() {
             match self.next_char() {
                 '#' => {
@@ -192,6 +465,25 @@ impl Parser {
                     self.consume_char();
                     selector.class.push(self.parse_identifier());
                 }
+                '[' => {
+                    selector.attribute.push(self.parse_attribute_selector()?);
+                }
+                ':' => {
+                    self.consume_char();
+                    let is_pseudo_element = self.peek().starts_with(':');
+                    if is_pseudo_element {
+                        self.consume_char();
+                    }
+                    let name = self.parse_identifier().to_ascii_lowercase();
+                    // The original pseudo-elements may have a single colon.
+                    match PseudoElement::from_name(&name) {
+                        Some(pseudo_element) => selector.pseudo_element = Some(pseudo_element),
+                        None if is_pseudo_element => {
+                            return Err(format!("unsupported pseudo-element `::{}`", name))
+                        }
+                        None => selector.pseudo_class.push(self.parse_pseudo_class(name)?),
+                    }
+                }
                 '*' => {
                     // universal selector
                     self.consume_char();
@@ -202,100 +494,323 @@ impl Parser {
                 _ => break,
             }
         }
-        selector
+        if self.pos == start {
+            return Err(self.unexpected("selector"));
+        }
+        Ok(selector)
+    }
+
+    /// Parse one attribute selector, e.g.: `[attr]` or `[attr^="value"]`
+    fn parse_attribute_selector(&mut self) -> Result<AttributeSelector, String> {
+        self.expect_char('[', "attribute selector")?;
+        self.advance();
+        let name = self.parse_identifier();
+        if name.is_empty() {
+            return Err(self.unexpected("attribute selector"));
+        }
+        self.advance();
+        if self.peek().starts_with(']') {
+            self.consume_char();
+            return Ok(AttributeSelector { name, operation: AttributeOperation::Exists });
+        }
+        let operation: fn(String) -> AttributeOperation =
+            match &*self.consume_while(|ch| "=~|^$*".contains(ch)) {
+                "=" => AttributeOperation::Equals,
+                "~=" => AttributeOperation::Includes,
+                "|=" => AttributeOperation::DashMatch,
+                "^=" => AttributeOperation::Prefix,
+                "$=" => AttributeOperation::Suffix,
+                "*=" => AttributeOperation::Substring,
+                op => return Err(format!("unexpected operator `{}` in attribute selector", op)),
+            };
+        self.advance();
+        let value = match self.peek().chars().next() {
+            Some('"') | Some('\'') => self.parse_string(),
+            Some(c) if valid_identifier_char(c) => self.parse_identifier(),
+            _ => return Err(self.unexpected("attribute selector")),
+        };
+        self.advance();
+        self.expect_char(']', "attribute selector")?;
+        Ok(AttributeSelector { name, operation: operation(value) })
+    }
+
+    /// Parse a pseudo-class, given its (lowercased) name, e.g.: `first-child`
+    /// or `nth-child` (followed by `(2n+1)`)
+    fn parse_pseudo_class(&mut self, name: String) -> Result<PseudoClass, String> {
+        if let Some(pseudo_class) = PseudoClass::from_name(&name) {
+            return Ok(pseudo_class);
+        }
+        Ok(match &*name {
+            "nth-child" => {
+                let (a, b) = self.parse_argument(Parser::parse_nth)?;
+                PseudoClass::NthChild(a, b)
+            }
+            "nth-of-type" => {
+                let (a, b) = self.parse_argument(Parser::parse_nth)?;
+                PseudoClass::NthOfType(a, b)
+            }
+            "not" => {
+                let selector = self.parse_argument(Parser::parse_simple_selector)?;
+                PseudoClass::Not(Box::new(selector))
+            }
+            _ => return Err(format!("unsupported pseudo-class `:{}`", name)),
+        })
+    }
+
+    /// Parse a parenthesized argument, e.g., of a functional pseudo-class.
+    fn parse_argument<T>(
+        &mut self,
+        parse: fn(&mut Self) -> Result<T, String>,
+    ) -> Result<T, String> {
+        self.expect_char('(', "argument")?;
+        self.advance();
+        let argument = parse(self)?;
+        self.advance();
+        self.expect_char(')', "argument")?;
+        Ok(argument)
+    }
+
+    /// Parse the argument of `:nth-child()` and the like, i.e., `an+b`, `odd`
+    /// or `even`, as `(a, b)`.
+    fn parse_nth(&mut self) -> Result<(i32, i32), String> {
+        let argument = self
+            .consume_while(|ch| ch != ')')
+            .chars()
+            .filter(|ch| !ch.is_whitespace())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        let parse_integer = |integer: &str| {
+            integer.parse::<i32>().map_err(|_| format!("malformed an+b `{}`", argument))
+        };
+        Ok(match argument.as_str() {
+            "odd" => (2, 1),
+            "even" => (2, 0),
+            _ => match argument.find('n') {
+                Some(n) => {
+                    let a = match &argument[..n] {
+                        "" | "+" => 1,
+                        "-" => -1,
+                        a => parse_integer(a)?,
+                    };
+                    let b = match &argument[n + 1..] {
+                        "" => 0,
+                        b => parse_integer(b)?,
+                    };
+                    (a, b)
+                }
+                None => (0, parse_integer(&argument)?),
+            },
+        })
     }
 
     /// Parse a list of declarations enclosed in `{ ... }`.
     fn parse_declarations(&mut self) -> Vec<Declaration> {
         assert_eq!(self.consume_char(), '{');
+        let declarations = self.parse_declaration_list();
+        // As in CSS, the end of input closes an unterminated block.
+        if !self.eof() {
+            assert_eq!(self.consume_char(), '}');
+        }
+        declarations
+    }
+
+    /// Parse declarations up to a closing `}` or the end of input, dropping
+    /// (with a warning) any that are malformed.
+    fn parse_declaration_list(&mut self) -> Vec<Declaration> {
         let mut declarations = Vec::new();
         loop {
             self.advance();
-            if self.next_char() == '}' {
-                self.consume_char();
+            if self.eof() || self.next_char() == '}' {
                 break;
             }
-            declarations.push(self.parse_declaration());
+            // Skip an empty declaration.
+            if self.next_char() == ';' {
+                self.consume_char();
+                continue;
+            }
+            let start = self.pos;
+            match self.parse_declaration() {
+                Ok(declaration) => declarations.push(declaration),
+                Err(reason) => {
+                    let error_pos = self.pos;
+                    self.skip_declaration();
+                    let source = self.input[start..self.pos].trim().to_string();
+                    self.warn(error_pos, format!("{}; dropped `{}`", reason, source));
+                }
+            }
         }
         declarations
     }
 
     /// Parse one `<property>: <value>;` declaration.
-    fn parse_declaration(&mut self) -> Declaration {
+    fn parse_declaration(&mut self) -> Result<Declaration, String> {
         let property_name = self.parse_identifier();
+        if property_name.is_empty() {
+            return Err(self.unexpected("declaration"));
+        }
         self.advance();
-        assert_eq!(self.consume_char(), ':');
-        self.advance();
-        let value = self.parse_value();
+        self.expect_char(':', "declaration")?;
         self.advance();
-        if self.next_char() != '}' {
-            assert_eq!(self.consume_char(), ';');
+        let mut value = Vec::new();
+        while !self.eof() && !matches!(self.next_char(), ';' | '}' | '!') {
+            value.push(self.parse_value()?);
+            self.advance();
+        }
+        if value.is_empty() {
+            return Err(format!("missing value for `{}`", property_name));
+        }
+        let important = !self.eof() && self.next_char() == '!';
+        if important {
+            self.consume_char();
+            self.advance();
+            if !self.parse_identifier().eq_ignore_ascii_case("important") {
+                return Err(String::from("expected `important` after `!`"));
+            }
+            self.advance();
+        }
+        if !self.eof() && !matches!(self.next_char(), ';' | '}') {
+            return Err(self.unexpected("declaration"));
+        }
+        if !self.eof() && self.next_char() == ';' {
+            self.consume_char();
         }
 
-        Declaration {
+        Ok(Declaration {
             name: property_name,
-            value: value,
+            value,
+            important,
+        })
+    }
+
+    /// Skip the rest of a malformed declaration, through the next `;` (but
+    /// not past the closing `}` of its rule).
+    fn skip_declaration(&mut self) {
+        self.consume_while(|ch| ch != ';' && ch != '}');
+        if !self.eof() && self.next_char() == ';' {
+            self.consume_char();
         }
     }
 
     // Methods for parsing values:
 
-    fn parse_value(&mut self) -> Value {
-        match self.next_char() {
-            '-' | '0'..='9' | '.' => self.parse_length(),
-            '#' => self.parse_color(),
-            _ => Value::Keyword(self.parse_identifier()),
+    fn parse_value(&mut self) -> Result<Value, String> {
+        Ok(match self.next_char() {
+            '-' | '0'..='9' | '.' => self.parse_length()?,
+            '#' => self.parse_color()?,
+            '"' | '\'' => Value::StringValue(self.parse_string()),
+            _ => {
+                let identifier = self.parse_identifier();
+                if identifier.is_empty() {
+                    return Err(self.unexpected("value"));
+                }
+                if !self.eof() && self.next_char() == '(' {
+                    let name = identifier.to_ascii_lowercase();
+                    let args = self.parse_argument(Parser::parse_arguments)?;
+                    match name.as_str() {
+                        "rgb" | "rgba" | "hsl" | "hsla" => Value::ColorValue(
+                            color_function(&name, &args)
+                                .ok_or_else(|| format!("invalid arguments to `{}()`", name))?,
+                        ),
+                        _ => Value::Function(name, args),
+                    }
+                } else {
+                    Value::Keyword(identifier)
+                }
+            }
+        })
+    }
+
+    /// Parse comma-separated function arguments, up to the closing `)`.
+    fn parse_arguments(&mut self) -> Result<Vec<Value>, String> {
+        let mut args = Vec::new();
+        while !self.eof() && !self.peek().starts_with(')') {
+            let start = self.pos;
+            args.push(self.parse_value()?);
+            if self.pos == start {
+                return Err(self.unexpected("function arguments"));
+            }
+            self.advance();
+            if self.peek().starts_with(',') {
+                self.consume_char();
+                self.advance();
+            }
         }
+        Ok(args)
     }
 
-    fn parse_length(&mut self) -> Value {
-        let number = self.parse_float();
-        if self.next_char() == '%' {
+    fn parse_length(&mut self) -> Result<Value, String> {
+        let number = self.parse_float()?;
+        Ok(if self.peek().starts_with('%') {
             self.consume_char();
             Value::Percent(number)
+        } else if !self.peek().starts_with(valid_identifier_char) {
+            Value::Number(number)
         } else {
-            Value::Length(number, self.parse_unit())
-        }
+            Value::Length(number, self.parse_unit()?)
+        })
     }
 
-    fn parse_float(&mut self) -> f32 {
-        self.consume_while(
-            |ch| match ch {
-                '-' | '0'..='9' | '.' => true,
-                _ => false
-            }
-        ).parse::<f32>().unwrap()
+    fn parse_float(&mut self) -> Result<f32, String> {
+        let number = self.consume_while(|ch| matches!(ch, '-' | '0'..='9' | '.'));
+        number.parse().map_err(|_| format!("malformed number `{}`", number))
     }
 
-    fn parse_unit(&mut self) -> Unit {
-        match &*self.parse_identifier().to_ascii_lowercase() {
-            "cm" => Unit::Cm,
-            "mm" => Unit::Mm,
-            "q" => Unit::Q,
-            "in" => Unit::In,
-            "pc" => Unit::Pc,
-            "pt" => Unit::Pt,
-            "px" => Unit::Px,
-            // "em" => Unit::Em,
-            _ => panic!("unrecognized unit"),
-        }
+    fn parse_unit(&mut self) -> Result<Unit, String> {
+        let name = self.parse_identifier();
+        Unit::from_name(&name).ok_or_else(|| format!("unknown unit `{}`", name))
     }
 
-    fn parse_color(&mut self) -> Value {
+    /// Parse a hexadecimal color, i.e.: `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa`
+    fn parse_color(&mut self) -> Result<Value, String> {
         assert_eq!(self.consume_char(), '#');
-        Value::ColorValue(Color {
-            r: self.parse_hex_pair(),
-            g: self.parse_hex_pair(),
-            b: self.parse_hex_pair(),
-            a: 255,
-        })
+        let digits = self.consume_while(|ch| ch.is_ascii_hexdigit());
+        let channel = |i: usize, width: usize| {
+            let value = u8::from_str_radix(&digits[i * width..(i + 1) * width], 16).unwrap();
+            // A single digit is doubled, e.g.: `f` ~ `ff`
+            if width == 1 { value * 17 } else { value }
+        };
+        let width = match digits.len() {
+            3 | 4 => 1,
+            6 | 8 => 2,
+            _ => return Err(format!("malformed hex color `#{}`", digits)),
+        };
+        let alpha = if digits.len() == 4 * width { channel(3, width) } else { 255 };
+        let (red, green, blue) = (channel(0, width), channel(1, width), channel(2, width));
+        Ok(Value::ColorValue(Color::rgba(red, green, blue, alpha)))
     }
 
-    /// Parse two hexadecimal digits.
-    fn parse_hex_pair(&mut self) -> u8 {
-        let s = &self.input[self.pos..self.pos + 2];
-        self.pos += 2;
-        u8::from_str_radix(s, 16).unwrap()
+    /// Parse a quoted string, e.g.: `"value"` or `'\A'`
+    fn parse_string(&mut self) -> String {
+        let quote = self.consume_char();
+        let mut string = String::new();
+        // As in CSS, the end of input closes an unterminated string.
+        while !self.eof() {
+            match self.consume_char() {
+                ch if ch == quote => break,
+                '\\' => string.extend(self.parse_escape()),
+                ch => string.push(ch),
+            }
+        }
+        string
+    }
+
+    /// Parse an escape sequence (after its `\`), i.e., up to six hexadecimal
+    /// digits and a whitespace character, or any other one character. An
+    /// escaped newline is dropped.
+    fn parse_escape(&mut self) -> Option<char> {
+        if self.eof() {
+            return None;
+        }
+        let hex_len = self.peek().chars().take(6).take_while(char::is_ascii_hexdigit).count();
+        if hex_len == 0 {
+            return Some(self.consume_char()).filter(|&ch| ch != '\n');
+        }
+        let code = u32::from_str_radix(&self.peek()[..hex_len], 16).unwrap();
+        self.pos += hex_len;
+        if !self.eof() && self.next_char().is_whitespace() {
+            self.consume_char();
+        }
+        Some(std::char::from_u32(code).filter(|&ch| ch != '\0').unwrap_or('\u{fffd}'))
     }
 
     /// Parse a property name or keyword.
@@ -311,16 +826,46 @@ impl Parser {
             // Consume the opening delimiter.
             self.consume_char();
             self.consume_char();
-            // Skip past the comment text.
-            self.pos += self.peek().find("*/").expect("Dangling comment!");
-            // Consume the closing delimiter.
-            self.consume_char();
-            self.consume_char();
+            // Skip past the comment text and the closing delimiter. As in CSS,
+            // the end of input closes an unterminated comment.
+            match self.peek().find("*/") {
+                Some(offset) => self.pos += offset + "*/".len(),
+                None => self.pos = self.input.len(),
+            }
             // Consume interleaving/trailing whitespace.
             self.consume_while(char::is_whitespace);
         }
     }
 
+    /// Describe the upcoming character (or the end of input) as unexpected in
+    /// the given context.
+    fn unexpected(&self, context: &str) -> String {
+        match self.peek().chars().next() {
+            Some(c) => format!("unexpected `{}` in {}", c, context),
+            None => format!("unexpected end of input in {}", context),
+        }
+    }
+
+    /// Record a warning positioned at the given byte offset of the input.
+    fn warn(&mut self, pos: usize, message: String) {
+        let before = &self.input[..pos];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        self.warnings.push(Warning {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            message,
+        });
+    }
+
+    /// Consume the expected character, or fail if any other comes next.
+    fn expect_char(&mut self, expected: char, context: &str) -> Result<(), String> {
+        if !self.peek().starts_with(expected) {
+            return Err(self.unexpected(context));
+        }
+        self.consume_char();
+        Ok(())
+    }
+
     /// Consume characters until `test` returns false.
     fn consume_while<F>(&mut self, pred: F) -> String
     where
@@ -359,9 +904,209 @@ impl Parser {
     }
 }
 
+/// Convert the arguments of `rgb()`, `rgba()`, `hsl()` or `hsla()` into a
+/// color, unless they're invalid. Either form of each function takes an
+/// optional alpha, as a number or percentage. The hue of `hsl()` is a number
+/// of degrees.
+fn color_function(name: &str, args: &[Value]) -> Option<Color> {
+    let alpha = match args.get(3) {
+        None => 1.0,
+        Some(Value::Number(alpha)) => *alpha,
+        Some(Value::Percent(alpha)) => alpha / 100.0,
+        Some(_) => return None,
+    };
+    match (name, args.get(0..3)?) {
+        ("rgb", channels) | ("rgba", channels) if args.len() <= 4 => {
+            // Channels are all numbers (out of 255) or all percentages.
+            let is_percent = |value: &Value| matches!(value, Value::Percent(_));
+            if channels.iter().any(is_percent) && !channels.iter().all(is_percent) {
+                return None;
+            }
+            let channel = |value: &Value| {
+                let level = match *value {
+                    Value::Number(level) => level / 255.0,
+                    Value::Percent(level) => level / 100.0,
+                    _ => return None,
+                };
+                Some((level.clamp(0.0, 1.0) * 255.0).round() as u8)
+            };
+            let (red, green, blue) =
+                (channel(&channels[0])?, channel(&channels[1])?, channel(&channels[2])?);
+            let alpha = (alpha.clamp(0.0, 1.0) * 255.0).round() as u8;
+            Some(Color::rgba(red, green, blue, alpha))
+        }
+        ("hsl", &[Value::Number(hue), Value::Percent(saturation), Value::Percent(lightness)])
+        | ("hsla", &[Value::Number(hue), Value::Percent(saturation), Value::Percent(lightness)])
+            if args.len() <= 4 =>
+        {
+            Some(Color::hsla(hue, saturation / 100.0, lightness / 100.0, alpha))
+        }
+        _ => None,
+    }
+}
+
 fn valid_identifier_char(c: char) -> bool {
     match c {
         'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => true, // TODO: Include U+00A0 and higher.
         _ => false,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(declarations: &[Declaration]) -> Vec<&str> {
+        declarations.iter().map(|declaration| declaration.name.as_str()).collect()
+    }
+
+    #[test]
+    fn parse_compound_selectors() {
+        let stylesheet = parse(String::from("ul>li  a.b, h1 ~ p + p, div {}"));
+        let selectors = &stylesheet.rules[0].selectors;
+        // Sorted by specificity, highest first.
+        let specificities: Vec<_> = selectors.iter().map(Selector::specificity).collect();
+        assert_eq!(specificities, [(0, 1, 3), (0, 0, 3), (0, 0, 1)]);
+        let (subject, context) = match selectors[0] {
+            Selector::Compound(ref subject, ref context) => (subject, context),
+            _ => panic!("expected a compound selector"),
+        };
+        assert_eq!(subject.tag.as_deref(), Some("a"));
+        assert_eq!(subject.class, ["b"]);
+        let context: Vec<_> =
+            context.iter().map(|(combinator, simple)| (*combinator, simple.tag.as_deref())).collect();
+        assert_eq!(context, [(Combinator::Descendant, Some("li")), (Combinator::Child, Some("ul"))]);
+        match selectors[1] {
+            Selector::Compound(_, ref context) => assert_eq!(
+                context.iter().map(|(combinator, _)| *combinator).collect::<Vec<_>>(),
+                [Combinator::NextSibling, Combinator::SubsequentSibling]
+            ),
+            _ => panic!("expected a compound selector"),
+        }
+        match selectors[2] {
+            Selector::Simple(ref simple) => assert_eq!(simple.tag.as_deref(), Some("div")),
+            _ => panic!("expected a simple selector"),
+        }
+    }
+
+    #[test]
+    fn invalid_selectors_drop_rule() {
+        let stylesheet = parse(String::from(
+            "a > { b {} } b {} c ) d, e {} , f {} [] {} [a=] {} [a!=b] {} [a=b {} \
+             a:hover, b {} :nth-child(x) {} :not(.a {} p::first-line {} g {x: y} h",
+        ));
+        let tags: Vec<_> = stylesheet
+            .rules
+            .iter()
+            .map(|rule| match rule.selectors[0] {
+                Selector::Simple(ref simple) => simple.tag.as_deref(),
+                _ => panic!("expected a simple selector"),
+            })
+            .collect();
+        assert_eq!(tags, [Some("b"), Some("g")]);
+    }
+
+    #[test]
+    fn dropped_rules_warn() {
+        let source = "p {}\n  a:hover, b { x: y }\nc ) d {}\ne { f: g /* h";
+        let (stylesheet, warnings) = parse_with_warnings(String::from(source));
+        assert_eq!(stylesheet.rules.len(), 2);
+        let warnings: Vec<_> = warnings.iter().map(Warning::to_string).collect();
+        assert_eq!(
+            warnings,
+            [
+                "2:10: unsupported pseudo-class `:hover`; dropped `a:hover, b { x: y }`",
+                "3:3: unexpected `)` in selector; dropped `c ) d {}`",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_color_values() {
+        let source = "a: #f80; b: #ff880080; c: rgb(255, 136, 0); d: RGBA(100%, 50%, 0%, 0.5); \
+                      e: hsl(32, 100%, 50%); f: hsla(0, 0%, 0%, 25%)";
+        let stylesheet = parse(format!("a {{ {} }}", source));
+        let colors: Vec<_> = stylesheet.rules[0]
+            .declarations
+            .iter()
+            .cloned()
+            .map(|declaration| declaration.value)
+            .collect();
+        let color = |r, g, b, a| vec![Value::ColorValue(Color::rgba(r, g, b, a))];
+        assert_eq!(
+            colors,
+            [
+                color(255, 136, 0, 255),
+                color(255, 136, 0, 128),
+                color(255, 136, 0, 255),
+                color(255, 128, 0, 128),
+                color(255, 136, 0, 255),
+                color(0, 0, 0, 64),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_color_functions() {
+        let (n, p) = (Value::Number(0.0), Value::Percent(0.0));
+        // Mixed channels, missing or extra arguments, and a non-percentage
+        // saturation are all invalid.
+        assert!(color_function("rgb", &[n.clone(), p.clone(), n.clone()]).is_none());
+        assert!(color_function("rgb", &[n.clone(), n.clone()]).is_none());
+        assert!(color_function("rgba", &vec![n.clone(); 5]).is_none());
+        assert!(color_function("hsl", &[n.clone(), n.clone(), p.clone()]).is_none());
+        assert!(color_function("hsl", &[n, p.clone(), p]).is_some());
+    }
+
+    #[test]
+    fn parse_declarations_skips_empty_and_malformed() {
+        let parse = |source: &str| parse_declarations(String::from(source)).0;
+        assert!(parse(";").is_empty());
+        assert!(parse("a;;").is_empty());
+        assert!(parse("color").is_empty());
+        assert!(parse("color:").is_empty());
+        let declarations = parse("color: red;; : 1px; margin: 0 !bogus; float: left");
+        assert_eq!(names(&declarations), ["color", "float"]);
+    }
+
+    #[test]
+    fn parse_declarations_values() {
+        let (declarations, warnings) =
+            parse_declarations(String::from("margin: 0 auto 1px 50% !important"));
+        assert!(warnings.is_empty());
+        assert_eq!(names(&declarations), ["margin"]);
+        assert!(declarations[0].important);
+        assert_eq!(
+            declarations[0].value,
+            [
+                Value::Number(0.0),
+                Value::Keyword(String::from("auto")),
+                Value::Length(1.0, Unit::Px),
+                Value::Percent(50.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_values_drop_declaration() {
+        // Unknown keywords are left for styling to ignore.
+        let source = "width: foo; color: #12;\nwidth: 10foo; color: @red; font-size: small";
+        let (declarations, warnings) = parse_declarations(String::from(source));
+        assert_eq!(names(&declarations), ["width", "font-size"]);
+        let warnings: Vec<_> = warnings.iter().map(Warning::to_string).collect();
+        assert_eq!(
+            warnings,
+            [
+                "1:23: malformed hex color `#12`; dropped `color: #12;`",
+                "2:13: unknown unit `foo`; dropped `width: 10foo;`",
+                "2:22: unexpected `@` in value; dropped `color: @red;`",
+            ]
+        );
+
+        // Likewise within a stylesheet, where the rest of the rule is kept.
+        let (stylesheet, warnings) =
+            parse_with_warnings(String::from("p { color: #12; width: 10foo; height: 1px }"));
+        assert_eq!(names(&stylesheet.rules[0].declarations), ["height"]);
+        assert_eq!(warnings.len(), 2);
+    }
+}
@@ -1,22 +1,57 @@
 //! Basic DOM data structures.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
 
 pub struct DocumentTree {
     pub document_root: DocumentNode,
+    /// Location of each node, indexed by node index.
+    slots: Vec<NodeSlot>,
+    /// Roots of the subtrees modified since the last `clear_dirty()`.
+    dirty: BTreeSet<NodeIndex>,
 }
 
 /// A node of the document tree (a baby DOM tree).
 pub struct DocumentNode {
-    pub index: NodeIndex, // N.B., meaningless for text!
+    pub index: NodeIndex,
+    /// Index among elements alone, as in Cassius `:elt` references (`None`
+    /// for text).
+    pub element_index: Option<NodeIndex>,
     pub children: Vec<DocumentNode>,
     pub node_type: NodeType,
+    /// Was this subtree modified since the tree's last `clear_dirty()`?
+    dirty: bool,
 }
 
 /// Preorder-assigned node indices.
 pub type NodeIndex = u32;
 
-#[derive(Clone, Debug)]
+/// The location of a node, as its parent's index and its position among its
+/// parent's children, along with the extent of its subtree.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct NodeSlot {
+    parent: Option<NodeIndex>,
+    position: usize,
+    /// Index of the last node in this node's subtree (possibly itself).
+    last_descendant: NodeIndex,
+}
+
+/// A preorder traversal over a subtree of document nodes.
+pub struct Preorder<'a> {
+    stack: Vec<&'a DocumentNode>,
+}
+
+impl<'a> Iterator for Preorder<'a> {
+    type Item = &'a DocumentNode;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.stack.extend(node.children.iter().rev());
+        Some(node)
+    }
+}
+
+#[derive(Clone, Default, Debug)]
 pub struct AttributeMap(HashMap<String, String>);
 
 #[derive(Clone, Debug)]
@@ -32,14 +67,257 @@ pub struct ElementData {
 }
 
 impl DocumentTree {
+    /// Create a document tree from its top-level nodes, wrapping them in an
+    /// `html` element unless there's exactly one. Whitespace-only text outside
+    /// of the root element is ignored.
     pub fn new(root_nodes: Vec<DocumentNode>) -> Self {
-        let mut document_root = if root_nodes.len() == 1 {
+        let root_nodes: Vec<_> = root_nodes
+            .into_iter()
+            .filter(|node| node.as_text().map_or(true, |text| !text.trim().is_empty()))
+            .collect();
+        let document_root = if root_nodes.len() == 1 {
             root_nodes.into_iter().next().unwrap()
         } else {
             DocumentNode::new_elem("html".to_string(), AttributeMap::default(), root_nodes)
         };
-        document_root.number_preorder();
-        DocumentTree { document_root }
+        let mut document_tree = DocumentTree {
+            document_root,
+            slots: Vec::new(),
+            dirty: BTreeSet::new(),
+        };
+        document_tree.number_preorder();
+        document_tree
+    }
+
+    /// Number every node (text included) in preorder and rebuild the lookup
+    /// table from node indices to nodes, along with the dirty set.
+    ///
+    /// See `DocumentNode::number_preorder()`.
+    fn number_preorder(&mut self) {
+        self.slots.clear();
+        self.document_root.number_preorder(None, 0, &mut self.slots, &mut 0);
+        self.dirty = self
+            .document_root
+            .preorder()
+            .filter(|node| node.dirty)
+            .map(|node| node.index)
+            .collect();
+    }
+
+    /// Find the path of child positions from the root to the node with the
+    /// given index.
+    fn path(&self, index: NodeIndex) -> Option<Vec<usize>> {
+        let mut path = Vec::new();
+        let mut slot = self.slots.get(index as usize)?;
+        while let Some(parent) = slot.parent {
+            path.push(slot.position);
+            slot = &self.slots[parent as usize];
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Lookup the node with the given index.
+    pub fn node(&self, index: NodeIndex) -> Option<&DocumentNode> {
+        let mut node = &self.document_root;
+        for position in self.path(index)? {
+            node = &node.children[position];
+        }
+        Some(node)
+    }
+
+    /// Lookup the node with the given index, for mutation. Callers must
+    /// renumber the tree if they change its structure.
+    fn node_mut(&mut self, index: NodeIndex) -> Option<&mut DocumentNode> {
+        let path = self.path(index)?;
+        let mut node = &mut self.document_root;
+        for position in path {
+            node = &mut node.children[position];
+        }
+        Some(node)
+    }
+
+    /// Count the nodes in the document tree.
+    pub fn node_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Find the parent of a node, if it's not the root.
+    pub fn parent(&self, index: NodeIndex) -> Option<NodeIndex> {
+        self.slots.get(index as usize)?.parent
+    }
+
+    /// Find the sibling immediately before a node.
+    pub fn prev_sibling(&self, index: NodeIndex) -> Option<NodeIndex> {
+        let position = self.slots.get(index as usize)?.position.checked_sub(1)?;
+        self.sibling_at(index, position)
+    }
+
+    /// Find the sibling immediately after a node.
+    pub fn next_sibling(&self, index: NodeIndex) -> Option<NodeIndex> {
+        let position = self.slots.get(index as usize)?.position + 1;
+        self.sibling_at(index, position)
+    }
+
+    /// Find the node at a given position among the siblings of a node.
+    fn sibling_at(&self, index: NodeIndex, position: usize) -> Option<NodeIndex> {
+        let parent = self.node(self.parent(index)?)?;
+        parent.children.get(position).map(|sibling| sibling.index)
+    }
+
+    /// Iterate over the ancestors of a node, from its parent up to the root.
+    pub fn ancestors<'a>(&'a self, index: NodeIndex) -> impl Iterator<Item = NodeIndex> + 'a {
+        std::iter::successors(self.parent(index), move |&ancestor| self.parent(ancestor))
+    }
+
+    /// Iterate over the (proper) descendants of a node, in preorder.
+    pub fn descendants(&self, index: NodeIndex) -> impl Iterator<Item = NodeIndex> {
+        let last = self.slots.get(index as usize).map_or(index, |slot| slot.last_descendant);
+        (index + 1)..=last
+    }
+
+    /// Find the first element, in preorder, with the given ID.
+    pub fn get_element_by_id(&self, id: &str) -> Option<NodeIndex> {
+        self.document_root
+            .preorder()
+            .find(|node| node.as_elem().and_then(ElementData::id) == Some(id))
+            .map(|node| node.index)
+    }
+
+    /// Find all elements, in preorder, with the given class.
+    pub fn get_elements_by_class(&self, class: &str) -> Vec<NodeIndex> {
+        self.document_root
+            .preorder()
+            .filter(|node| node.as_elem().map_or(false, |elem| elem.classes().contains(class)))
+            .map(|node| node.index)
+            .collect()
+    }
+
+    /// Insert a node (with its subtree) among the children of an element, at
+    /// the given position, and return its index. Returns `None`, dropping the
+    /// node, if the parent isn't an element or the position is out of range.
+    ///
+    /// The parent is marked dirty, and nodes following the inserted subtree
+    /// in preorder are renumbered.
+    pub fn insert_child(
+        &mut self,
+        parent: NodeIndex,
+        position: usize,
+        node: DocumentNode,
+    ) -> Option<NodeIndex> {
+        let parent_node = self.node_mut(parent)?;
+        if !parent_node.is_elem() || position > parent_node.children.len() {
+            return None;
+        }
+        parent_node.children.insert(position, node);
+        parent_node.dirty = true;
+        self.number_preorder();
+        self.node(parent).map(|parent_node| parent_node.children[position].index)
+    }
+
+    /// Insert a node (with its subtree) after the last child of an element.
+    ///
+    /// See `insert_child()`.
+    pub fn append_child(&mut self, parent: NodeIndex, node: DocumentNode) -> Option<NodeIndex> {
+        let position = self.node(parent)?.children.len();
+        self.insert_child(parent, position, node)
+    }
+
+    /// Remove a node (with its subtree) from the document and return it. The
+    /// root can't be removed (see `replace()`).
+    ///
+    /// The parent is marked dirty, and nodes following the removed subtree in
+    /// preorder are renumbered. The indices within the removed subtree are
+    /// left stale.
+    pub fn remove(&mut self, index: NodeIndex) -> Option<DocumentNode> {
+        let NodeSlot { parent, position, .. } = *self.slots.get(index as usize)?;
+        let parent_node = self.node_mut(parent?)?;
+        let removed = parent_node.children.remove(position);
+        parent_node.dirty = true;
+        self.number_preorder();
+        Some(removed)
+    }
+
+    /// Replace a node (with its subtree), possibly the root, by another and
+    /// return the replaced node.
+    ///
+    /// The replacement is marked dirty, and it and the nodes following it in
+    /// preorder are renumbered. The indices within the replaced subtree are
+    /// left stale.
+    pub fn replace(&mut self, index: NodeIndex, mut node: DocumentNode) -> Option<DocumentNode> {
+        node.dirty = true;
+        let replaced = std::mem::replace(self.node_mut(index)?, node);
+        self.number_preorder();
+        Some(replaced)
+    }
+
+    /// Set an attribute of an element, returning its previous value, if any.
+    /// Does nothing to text nodes.
+    ///
+    /// The element is marked dirty.
+    pub fn set_attribute(&mut self, index: NodeIndex, name: &str, value: String) -> Option<String> {
+        let node = self.node_mut(index)?;
+        let previous = match node.node_type {
+            NodeType::Element(ref mut elem) => elem.attributes.insert(name, value),
+            NodeType::Text(_) => return None,
+        };
+        node.dirty = true;
+        self.dirty.insert(index);
+        previous
+    }
+
+    /// Remove an attribute of an element, returning its value, if any.
+    ///
+    /// The element is marked dirty if the attribute was present.
+    pub fn remove_attribute(&mut self, index: NodeIndex, name: &str) -> Option<String> {
+        let node = self.node_mut(index)?;
+        let removed = match node.node_type {
+            NodeType::Element(ref mut elem) => elem.attributes.remove(name)?,
+            NodeType::Text(_) => return None,
+        };
+        node.dirty = true;
+        self.dirty.insert(index);
+        Some(removed)
+    }
+
+    /// The roots of the subtrees modified since the last `clear_dirty()`, by
+    /// current node index. A subtree may be listed within another.
+    pub fn dirty_subtrees(&self) -> &BTreeSet<NodeIndex> {
+        &self.dirty
+    }
+
+    /// Mark every subtree clean, e.g., once dependent layout is updated.
+    pub fn clear_dirty(&mut self) {
+        for index in std::mem::take(&mut self.dirty) {
+            if let Some(node) = self.node_mut(index) {
+                node.dirty = false;
+            }
+        }
+    }
+
+    /// Collapse whitespace throughout the document as per CSS `white-space:
+    /// normal`, except within preformatted (e.g., `pre`) and raw text (e.g.,
+    /// `script`) elements.
+    ///
+    /// Each run of whitespace becomes a single space, a space following
+    /// another space (even across inline element boundaries) is removed, and
+    /// spaces at the start or end of a line are removed. Text nodes left empty
+    /// are removed. Since this is a DOM pass, lines are broken only around
+    /// elements that are block-level by default (e.g., `div` or `p`) and `br`.
+    ///
+    /// https://www.w3.org/TR/css-text-3/#white-space-phase-1
+    pub fn collapse_whitespace(&mut self) {
+        self.document_root.collapse_leading_whitespace(&mut true);
+        self.document_root.collapse_trailing_whitespace(&mut true);
+        self.document_root.remove_empty_text();
+        self.number_preorder();
+    }
+}
+
+/// Serialize the document as HTML, per `html::serialize_document()`.
+impl fmt::Display for DocumentTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&crate::html::serialize_document(self, false))
     }
 }
 
@@ -51,8 +329,10 @@ impl DocumentNode {
     pub fn new_text(data: String) -> Self {
         DocumentNode {
             index: 0,
+            element_index: None,
             children: vec![],
             node_type: NodeType::Text(data),
+            dirty: false,
         }
     }
 
@@ -63,11 +343,13 @@ impl DocumentNode {
     pub fn new_elem(tag_name: String, attr_map: AttributeMap, children: Vec<Self>) -> Self {
         DocumentNode {
             index: 0,
-            children: children,
+            element_index: None,
+            children,
             node_type: NodeType::Element(ElementData {
                 tag: tag_name,
                 attributes: attr_map,
             }),
+            dirty: false,
         }
     }
 
@@ -97,18 +379,174 @@ impl DocumentNode {
         self.as_elem().map(|elem| elem.tag.as_ref())
     }
 
-    /// Number each node in the document tree such that assigned node indices
-    /// ascend with preorder traversal, also returning the maximal node index.
+    /// Traverse this node and its descendants in preorder.
+    pub fn preorder(&self) -> Preorder<'_> {
+        Preorder { stack: vec![self] }
+    }
+
+    /// Number each node in this subtree such that assigned node indices
+    /// ascend with preorder traversal, recording the location of each node in
+    /// `slots` (itself indexed by node index). Elements are also numbered
+    /// among themselves in preorder, counting from `element_count`.
     ///
-    /// Note that this method considers the first document node on which it's
-    /// invoked as the tree root, assigning it node index 0.
-    fn number_preorder(&mut self) -> NodeIndex {
-        let mut i = self.index;
-        for child in self.children.iter_mut().filter(|node| node.is_elem()) {
-            child.index = i + 1;
-            i = child.number_preorder();
+    /// Note that the first node numbered (i.e., with empty `slots`) is the
+    /// tree root, assigned node index 0.
+    fn number_preorder(
+        &mut self,
+        parent: Option<NodeIndex>,
+        position: usize,
+        slots: &mut Vec<NodeSlot>,
+        element_count: &mut NodeIndex,
+    ) {
+        self.index = slots.len() as NodeIndex;
+        slots.push(NodeSlot { parent, position, last_descendant: self.index });
+        self.element_index = if self.is_elem() {
+            *element_count += 1;
+            Some(*element_count - 1)
+        } else {
+            None
+        };
+        for (position, child) in self.children.iter_mut().enumerate() {
+            child.number_preorder(Some(self.index), position, slots, element_count);
+        }
+        slots[self.index as usize].last_descendant = slots.len() as NodeIndex - 1;
+    }
+}
+
+/// How an element participates in the whitespace processing of its parent's
+/// lines, based on the element's default display.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum WhitespaceRole {
+    /// Breaks the line around itself and processes its own lines.
+    Block,
+    /// Breaks the line around itself and preserves its whitespace.
+    Preformatted,
+    /// Breaks the line around itself and leaves its raw text (e.g., a script)
+    /// untouched.
+    RawText,
+    /// Forces a line break (i.e., `br`).
+    LineBreak,
+    /// Occupies inline space without text of its own (e.g., `img`).
+    Atomic,
+    /// Continues its parent's lines.
+    Inline,
+}
+
+impl WhitespaceRole {
+    fn of_tag(tag: &str) -> Self {
+        match tag {
+            "pre" | "listing" | "plaintext" | "textarea" => WhitespaceRole::Preformatted,
+            "script" | "style" => WhitespaceRole::RawText,
+            "br" => WhitespaceRole::LineBreak,
+            "img" | "input" | "button" | "select" | "iframe" | "video" | "audio" | "canvas"
+            | "object" | "embed" | "svg" => WhitespaceRole::Atomic,
+            "html" | "head" | "body" | "address" | "article" | "aside" | "blockquote"
+            | "center" | "dd" | "details" | "dir" | "div" | "dl" | "dt" | "fieldset"
+            | "figcaption" | "figure" | "footer" | "form" | "frame" | "frameset" | "h1"
+            | "h2" | "h3" | "h4" | "h5" | "h6" | "header" | "hgroup" | "hr" | "li" | "main"
+            | "menu" | "nav" | "noframes" | "ol" | "p" | "section" | "summary" | "ul"
+            | "table" | "caption" | "colgroup" | "col" | "thead" | "tbody" | "tfoot" | "tr"
+            | "td" | "th" | "title" => WhitespaceRole::Block,
+            _ => WhitespaceRole::Inline,
+        }
+    }
+}
+
+/// Is this character collapsible whitespace?
+fn is_collapsible_space(ch: char) -> bool {
+    matches!(ch, ' ' | '\t' | '\n' | '\r' | '\x0c')
+}
+
+impl DocumentNode {
+    /// Collapse whitespace runs and remove spaces at line starts, in document
+    /// order. The flag records whether the previous character was a space or
+    /// a line start.
+    fn collapse_leading_whitespace(&mut self, after_space: &mut bool) {
+        let role = match self.node_type {
+            NodeType::Text(ref mut text) => {
+                let mut collapsed = String::with_capacity(text.len());
+                for ch in text.chars() {
+                    if !is_collapsible_space(ch) {
+                        collapsed.push(ch);
+                        *after_space = false;
+                    } else if !*after_space {
+                        collapsed.push(' ');
+                        *after_space = true;
+                    }
+                }
+                *text = collapsed;
+                return;
+            }
+            NodeType::Element(ref elem) => WhitespaceRole::of_tag(&elem.tag),
+        };
+        match role {
+            WhitespaceRole::Block => {
+                let mut line_start = true;
+                for child in &mut self.children {
+                    child.collapse_leading_whitespace(&mut line_start);
+                }
+                *after_space = true;
+            }
+            WhitespaceRole::Preformatted => {
+                // As in HTML, a newline right after the opening tag is dropped.
+                if let Some(NodeType::Text(text)) = self.children.first_mut().map(|c| &mut c.node_type) {
+                    if text.starts_with('\n') {
+                        text.remove(0);
+                    }
+                }
+                *after_space = true;
+            }
+            WhitespaceRole::RawText | WhitespaceRole::LineBreak => *after_space = true,
+            WhitespaceRole::Atomic => *after_space = false,
+            WhitespaceRole::Inline => {
+                for child in &mut self.children {
+                    child.collapse_leading_whitespace(after_space);
+                }
+            }
+        }
+    }
+
+    /// Remove spaces at line ends, in reverse document order. The flag records
+    /// whether the next character is a line end.
+    fn collapse_trailing_whitespace(&mut self, before_break: &mut bool) {
+        let role = match self.node_type {
+            NodeType::Text(ref mut text) => {
+                if *before_break && text.ends_with(' ') {
+                    text.pop();
+                }
+                if !text.is_empty() {
+                    *before_break = false;
+                }
+                return;
+            }
+            NodeType::Element(ref elem) => WhitespaceRole::of_tag(&elem.tag),
+        };
+        match role {
+            WhitespaceRole::Block => {
+                let mut line_end = true;
+                for child in self.children.iter_mut().rev() {
+                    child.collapse_trailing_whitespace(&mut line_end);
+                }
+                *before_break = true;
+            }
+            WhitespaceRole::Preformatted | WhitespaceRole::RawText | WhitespaceRole::LineBreak => {
+                *before_break = true
+            }
+            WhitespaceRole::Atomic => *before_break = false,
+            WhitespaceRole::Inline => {
+                for child in self.children.iter_mut().rev() {
+                    child.collapse_trailing_whitespace(before_break);
+                }
+            }
+        }
+    }
+
+    /// Remove empty text nodes throughout this subtree.
+    fn remove_empty_text(&mut self) {
+        self.children.retain(|child| child.as_text().map_or(true, |text| !text.is_empty()));
+        for child in &mut self.children {
+            child.remove_empty_text();
         }
-        i
     }
 }
 
@@ -128,17 +566,157 @@ impl ElementData {
 }
 
 impl AttributeMap {
+    /// Create an attribute map, lowercasing the attribute names.
     pub fn new(attr_map: HashMap<String, String>) -> Self {
-        AttributeMap(attr_map)
+        AttributeMap(
+            attr_map
+                .into_iter()
+                .map(|(name, value)| (name.to_ascii_lowercase(), value))
+                .collect()
+        )
     }
 
+    /// Lookup an attribute's value by its (ASCII case-insensitive) name, as
+    /// in an HTML document.
     pub fn lookup(&self, attribute: &str) -> Option<&str> {
-        self.0.get(attribute).map(String::as_str)
+        if attribute.bytes().any(|b| b.is_ascii_uppercase()) {
+            self.0.get(&attribute.to_ascii_lowercase())
+        } else {
+            self.0.get(attribute)
+        }.map(String::as_str)
+    }
+
+    /// Iterate over the attributes, as (name, value) pairs, in no particular
+    /// order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Set an attribute, lowercasing its name, and return its previous value.
+    pub fn insert(&mut self, attribute: &str, value: String) -> Option<String> {
+        self.0.insert(attribute.to_ascii_lowercase(), value)
+    }
+
+    /// Remove an attribute by its (ASCII case-insensitive) name and return its
+    /// value.
+    pub fn remove(&mut self, attribute: &str) -> Option<String> {
+        self.0.remove(&attribute.to_ascii_lowercase())
     }
 }
 
-impl Default for AttributeMap {
-    fn default() -> Self {
-        AttributeMap(HashMap::default())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> DocumentTree {
+        crate::html::parse_document(String::from(source)).unwrap()
+    }
+
+    /// The text nodes of a subtree, in preorder.
+    fn texts(node: &DocumentNode) -> Vec<&str> {
+        node.as_text().into_iter().chain(node.children.iter().flat_map(texts)).collect()
+    }
+
+    fn elem(tag: &str, children: Vec<DocumentNode>) -> DocumentNode {
+        DocumentNode::new_elem(String::from(tag), AttributeMap::default(), children)
+    }
+
+    #[test]
+    fn collapse_whitespace_across_inline_and_block_boundaries() {
+        let document =
+            parse("<html>\n <body>\n  <p> a  <b> b </b>\tc </p>\n <div>d <br>\n e<img> f</div></body></html>");
+        assert_eq!(texts(&document.document_root), ["a ", "b ", "c", "d", "e", " f"]);
+    }
+
+    #[test]
+    fn collapse_whitespace_preserves_pre_and_raw_text() {
+        let document = parse(
+            "<html><pre>\n a  b\n</pre><p> x </p><style>\n a  {}\n</style><script> if (a  <b) </script></html>",
+        );
+        assert_eq!(texts(&document.document_root), [" a  b\n", "x", "\n a  {}\n", " if (a  <b) "]);
+    }
+
+    #[test]
+    fn number_preorder_with_text() {
+        let document = parse("<html><p>a<b>b</b></p>c<i></i></html>");
+        assert_eq!(document.node_count(), 7);
+        let tags: Vec<_> = (0..7).map(|index| document.node(index).unwrap().tag()).collect();
+        assert_eq!(tags, [Some("html"), Some("p"), None, Some("b"), None, None, Some("i")]);
+        assert_eq!(document.node(5).unwrap().as_text(), Some("c"));
+        assert!(document.node(7).is_none());
+        let element_indices: Vec<_> =
+            (0..7).map(|index| document.node(index).unwrap().element_index).collect();
+        assert_eq!(element_indices, [Some(0), Some(1), None, Some(2), None, None, Some(3)]);
+    }
+
+    #[test]
+    fn navigate_and_query() {
+        let document = parse("<html><p id=x class='a b'>a<b class=b>b</b></p>c<i id=x></i></html>");
+        assert_eq!(document.parent(2), Some(1));
+        assert_eq!(document.parent(0), None);
+        assert_eq!(document.prev_sibling(3), Some(2));
+        assert_eq!(document.prev_sibling(2), None);
+        assert_eq!(document.next_sibling(1), Some(5));
+        assert_eq!(document.next_sibling(6), None);
+        assert_eq!(document.next_sibling(0), None);
+        assert_eq!(document.ancestors(4).collect::<Vec<_>>(), [3, 1, 0]);
+        assert_eq!(document.descendants(1).collect::<Vec<_>>(), [2, 3, 4]);
+        assert_eq!(document.descendants(6).count(), 0);
+        assert_eq!(document.get_element_by_id("x"), Some(1));
+        assert_eq!(document.get_element_by_id("y"), None);
+        assert_eq!(document.get_elements_by_class("b"), [1, 3]);
+        assert_eq!(document.get_elements_by_class("a"), [1]);
+    }
+
+    #[test]
+    fn mutations_renumber_and_mark_dirty() {
+        let mut document = parse("<html><p>a<b>b</b></p>c<i></i></html>");
+        assert!(document.dirty_subtrees().is_empty());
+
+        // Inserting renumbers the nodes after the new subtree.
+        let em = elem("em", vec![DocumentNode::new_text(String::from("z"))]);
+        assert_eq!(document.insert_child(1, 1, em), Some(3));
+        assert_eq!(document.node_count(), 9);
+        assert_eq!(document.node(4).unwrap().as_text(), Some("z"));
+        assert_eq!(document.node(5).unwrap().tag(), Some("b"));
+        assert_eq!(document.node(8).unwrap().element_index, Some(4));
+        assert_eq!(document.dirty_subtrees().iter().collect::<Vec<_>>(), [&1]);
+        assert_eq!(document.insert_child(2, 0, elem("em", vec![])), None);
+        assert_eq!(document.insert_child(1, 4, elem("em", vec![])), None);
+        document.clear_dirty();
+        assert!(document.dirty_subtrees().is_empty());
+
+        assert_eq!(document.append_child(0, elem("u", vec![])), Some(9));
+        assert_eq!(document.dirty_subtrees().iter().collect::<Vec<_>>(), [&0]);
+        document.clear_dirty();
+
+        // Removing renumbers the nodes after the removed subtree.
+        let removed = document.remove(3).unwrap();
+        assert_eq!(removed.tag(), Some("em"));
+        assert_eq!(document.node_count(), 8);
+        assert_eq!(document.node(3).unwrap().tag(), Some("b"));
+        assert_eq!(document.parent(4), Some(3));
+        assert_eq!(document.dirty_subtrees().iter().collect::<Vec<_>>(), [&1]);
+        assert!(document.remove(0).is_none());
+        document.clear_dirty();
+
+        // Replacing marks the replacement itself dirty.
+        let replaced = document.replace(3, elem("s", vec![])).unwrap();
+        assert_eq!(replaced.tag(), Some("b"));
+        assert_eq!(document.node_count(), 7);
+        assert_eq!(document.node(4).unwrap().as_text(), Some("c"));
+        assert_eq!(document.dirty_subtrees().iter().collect::<Vec<_>>(), [&3]);
+        document.clear_dirty();
+
+        assert_eq!(document.set_attribute(5, "ID", String::from("q")), None);
+        assert_eq!(document.set_attribute(5, "id", String::from("r")), Some(String::from("q")));
+        assert_eq!(document.get_element_by_id("r"), Some(5));
+        assert_eq!(document.dirty_subtrees().iter().collect::<Vec<_>>(), [&5]);
+        document.clear_dirty();
+        assert_eq!(document.set_attribute(4, "id", String::from("t")), None);
+        assert!(document.dirty_subtrees().is_empty());
+        assert_eq!(document.remove_attribute(5, "Id"), Some(String::from("r")));
+        assert_eq!(document.remove_attribute(5, "id"), None);
+        assert_eq!(document.dirty_subtrees().iter().collect::<Vec<_>>(), [&5]);
     }
 }
@@ -1,143 +1,800 @@
 //! A simple parser for a tiny subset of HTML.
 //!
-//! Can parse basic opening and closing tags, and text nodes.
+//! Can parse basic opening and closing tags and text nodes (with character
+//! references). The contents of `<script>` and `<style>` are kept verbatim as
+//! raw text. Comments, doctypes and processing instructions are skipped, as
+//! are CDATA sections, which HTML treats as bogus comments. Void elements
+//! (e.g., `<br>`) need no closing tag, and optional closing tags (e.g., `</p>`
+//! or `</li>`) are implied as in HTML. Attribute values may be quoted, unquoted
+//! or omitted (for boolean attributes). Tag and attribute names are lowercased.
+//! Whitespace is collapsed as per `dom::DocumentTree::collapse_whitespace()`.
+//!
+//! By default, the parser rejects non-well-formed markup with a `ParseError`.
+//! In lenient mode, it instead recovers from mismatched closing tags, stray
+//! `<` characters and malformed attributes, roughly as a browser would.
+//!
+//! Document trees can be serialized back to HTML with `serialize_document`.
 //!
 //! Not yet supported:
 //!
-//! * Comments
-//! * Doctypes and processing instructions
-//! * Non-well-formed markup
-//! * Character entities
+//! * Named character references outside of HTML 4 (except `&apos;`)
 
-use crate::dom::{AttributeMap, DocumentNode, DocumentTree};
+use crate::dom::{AttributeMap, DocumentNode, DocumentTree, NodeType};
+use kuchiki::traits::TendrilSink;
 use std::collections::HashMap;
+use std::fmt;
 
 /// Parse an HTML document, number its tree nodes, and return the initialized
 /// document tree.
 ///
-/// See `dom::DocumentNode::number_preorder()` for information on node numbering.
-pub fn parse_document(source: String) -> DocumentTree {
-    DocumentTree::new(Parser::new(source).parse_document())
+/// See `dom::DocumentTree::number_preorder()` for information on node numbering.
+pub fn parse_document(source: String) -> Result<DocumentTree, ParseError> {
+    Parser::new(source).parse_document().map(build_tree)
+}
+
+/// Parse an HTML document like `parse_document`, but recover from malformed
+/// markup wherever possible.
+pub fn parse_document_lenient(source: String) -> Result<DocumentTree, ParseError> {
+    Parser::new_lenient(source).parse_document().map(build_tree)
+}
+
+/// Parse an HTML document with the spec-compliant html5ever parser (via
+/// kuchiki), number its tree nodes, and return the initialized document tree.
+///
+/// Comments, doctypes and processing instructions are dropped, and whitespace
+/// is collapsed, as by `parse_document`, so that the resulting trees (and
+/// their node numbering) can be compared directly.
+pub fn parse_document_spec(source: String) -> DocumentTree {
+    let document = kuchiki::parse_html().one(source);
+    build_tree(document.children().filter_map(|node| convert_spec_node(&node)).collect())
+}
+
+/// Assemble a document tree from parsed top-level nodes, with whitespace
+/// collapsed.
+fn build_tree(root_nodes: Vec<DocumentNode>) -> DocumentTree {
+    let mut document_tree = DocumentTree::new(root_nodes);
+    document_tree.collapse_whitespace();
+    document_tree
+}
+
+/// Convert a kuchiki node to a document node, or `None` if it's ignored.
+fn convert_spec_node(node: &kuchiki::NodeRef) -> Option<DocumentNode> {
+    match node.data() {
+        kuchiki::NodeData::Element(elem) => {
+            let attributes = elem.attributes.borrow().map
+                .iter()
+                .map(|(name, attr)| (name.local.to_string(), attr.value.clone()))
+                .collect();
+            let children = node.children().filter_map(|child| convert_spec_node(&child)).collect();
+            Some(DocumentNode::new_elem(
+                elem.name.local.to_string(),
+                AttributeMap::new(attributes),
+                children,
+            ))
+        }
+        kuchiki::NodeData::Text(text) => Some(DocumentNode::new_text(text.borrow().clone())),
+        _ => None,
+    }
+}
+
+/// Serialize a document tree as HTML, escaping text and attribute values.
+/// Attributes are written in order by name, for reproducible output.
+///
+/// If `annotate` is set, each element is also given its element index (as in
+/// Cassius `:elt` references) as a `data-elt` attribute, ahead of its own
+/// attributes, unless it already has one. The text of raw text elements
+/// (`<script>` and `<style>`) is written as is.
+pub fn serialize_document(document: &DocumentTree, annotate: bool) -> String {
+    let mut html = String::new();
+    serialize_node(&document.document_root, annotate, &mut html);
+    html
+}
+
+fn serialize_node(node: &DocumentNode, annotate: bool, html: &mut String) {
+    let elem = match node.node_type {
+        NodeType::Element(ref elem) => elem,
+        NodeType::Text(ref text) => return escape_into(text, false, html),
+    };
+    html.push('<');
+    html.push_str(&elem.tag);
+    if annotate && elem.attributes.lookup("data-elt").is_none() {
+        if let Some(element_index) = node.element_index {
+            html.push_str(&format!(" data-elt=\"{}\"", element_index));
+        }
+    }
+    let mut attributes: Vec<_> = elem.attributes.iter().collect();
+    attributes.sort();
+    for (name, value) in attributes {
+        html.push(' ');
+        html.push_str(name);
+        html.push_str("=\"");
+        escape_into(value, true, html);
+        html.push('"');
+    }
+    html.push('>');
+    if is_void_element(&elem.tag) {
+        return;
+    }
+    // A leading newline would be dropped when reparsed (see
+    // `dom::DocumentTree::collapse_whitespace()`), so double it.
+    let preformatted = elem.tag == "pre" || elem.tag == "listing" || elem.tag == "textarea";
+    if preformatted && node.children.first().and_then(DocumentNode::as_text)
+        .map_or(false, |text| text.starts_with('\n'))
+    {
+        html.push('\n');
+    }
+    let raw_text = is_raw_text_element(&elem.tag);
+    for child in &node.children {
+        match child.as_text() {
+            Some(text) if raw_text => html.push_str(text),
+            _ => serialize_node(child, annotate, html),
+        }
+    }
+    html.push_str("</");
+    html.push_str(&elem.tag);
+    html.push('>');
+}
+
+/// Append text to serialized HTML, escaping it for use as text or (if
+/// `in_attribute` is set) as a double-quoted attribute value.
+fn escape_into(text: &str, in_attribute: bool, html: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '\u{a0}' => html.push_str("&nbsp;"),
+            '"' if in_attribute => html.push_str("&quot;"),
+            '<' if !in_attribute => html.push_str("&lt;"),
+            '>' if !in_attribute => html.push_str("&gt;"),
+            _ => html.push(c),
+        }
+    }
+}
+
+/// An unrecoverable syntax error, positioned by line and column (from 1).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Elements that never have contents and so never need a closing tag.
+const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param",
+    "source", "track", "wbr",
+];
+
+fn is_void_element(tag_name: &str) -> bool {
+    VOID_ELEMENTS.contains(&tag_name)
+}
+
+/// Can this element's closing tag be omitted?
+fn has_optional_end_tag(tag_name: &str) -> bool {
+    matches!(
+        tag_name,
+        "html" | "head" | "body" | "p" | "li" | "dt" | "dd" | "rt" | "rp" | "option"
+            | "optgroup" | "colgroup" | "caption" | "thead" | "tbody" | "tfoot" | "tr" | "td"
+            | "th"
+    )
+}
+
+/// Does an opening tag named `next` imply the closing tag of an open element
+/// named `open`?
+///
+/// See https://html.spec.whatwg.org/multipage/syntax.html#optional-tags
+fn is_implicitly_closed_by(open: &str, next: &str) -> bool {
+    match open {
+        "p" => matches!(
+            next,
+            "address" | "article" | "aside" | "blockquote" | "details" | "div" | "dl"
+                | "fieldset" | "figcaption" | "figure" | "footer" | "form" | "h1" | "h2"
+                | "h3" | "h4" | "h5" | "h6" | "header" | "hgroup" | "hr" | "main" | "menu"
+                | "nav" | "ol" | "p" | "pre" | "section" | "table" | "ul"
+        ),
+        "li" => next == "li",
+        "dt" | "dd" => next == "dt" || next == "dd",
+        "rt" | "rp" => next == "rt" || next == "rp",
+        "option" => next == "option" || next == "optgroup",
+        "optgroup" => next == "optgroup",
+        "head" => next == "body",
+        "thead" | "tbody" => next == "tbody" || next == "tfoot",
+        "tr" => matches!(next, "tr" | "thead" | "tbody" | "tfoot"),
+        "td" | "th" => matches!(next, "td" | "th" | "tr" | "thead" | "tbody" | "tfoot"),
+        _ => false,
+    }
+}
+
+/// Names of the character references for U+00A0 through U+00FF, in order.
+const LATIN1_ENTITIES: [&str; 96] = [
+    "nbsp", "iexcl", "cent", "pound", "curren", "yen", "brvbar", "sect", "uml", "copy",
+    "ordf", "laquo", "not", "shy", "reg", "macr", "deg", "plusmn", "sup2", "sup3",
+    "acute", "micro", "para", "middot", "cedil", "sup1", "ordm", "raquo", "frac14",
+    "frac12", "frac34", "iquest", "Agrave", "Aacute", "Acirc", "Atilde", "Auml",
+    "Aring", "AElig", "Ccedil", "Egrave", "Eacute", "Ecirc", "Euml", "Igrave", "Iacute",
+    "Icirc", "Iuml", "ETH", "Ntilde", "Ograve", "Oacute", "Ocirc", "Otilde", "Ouml",
+    "times", "Oslash", "Ugrave", "Uacute", "Ucirc", "Uuml", "Yacute", "THORN", "szlig",
+    "agrave", "aacute", "acirc", "atilde", "auml", "aring", "aelig", "ccedil", "egrave",
+    "eacute", "ecirc", "euml", "igrave", "iacute", "icirc", "iuml", "eth", "ntilde",
+    "ograve", "oacute", "ocirc", "otilde", "ouml", "divide", "oslash", "ugrave",
+    "uacute", "ucirc", "uuml", "yacute", "thorn", "yuml",
+];
+
+/// Look up the character named by a character reference (e.g., `amp`),
+/// among those of HTML 4 (plus `apos`).
+fn named_entity(name: &str) -> Option<char> {
+    let ch = match name {
+        "quot" => '\u{0022}',
+        "amp" => '\u{0026}',
+        "apos" => '\u{0027}',
+        "lt" => '\u{003c}',
+        "gt" => '\u{003e}',
+        "OElig" => '\u{0152}',
+        "oelig" => '\u{0153}',
+        "Scaron" => '\u{0160}',
+        "scaron" => '\u{0161}',
+        "Yuml" => '\u{0178}',
+        "fnof" => '\u{0192}',
+        "circ" => '\u{02c6}',
+        "tilde" => '\u{02dc}',
+        "Alpha" => '\u{0391}',
+        "Beta" => '\u{0392}',
+        "Gamma" => '\u{0393}',
+        "Delta" => '\u{0394}',
+        "Epsilon" => '\u{0395}',
+        "Zeta" => '\u{0396}',
+        "Eta" => '\u{0397}',
+        "Theta" => '\u{0398}',
+        "Iota" => '\u{0399}',
+        "Kappa" => '\u{039a}',
+        "Lambda" => '\u{039b}',
+        "Mu" => '\u{039c}',
+        "Nu" => '\u{039d}',
+        "Xi" => '\u{039e}',
+        "Omicron" => '\u{039f}',
+        "Pi" => '\u{03a0}',
+        "Rho" => '\u{03a1}',
+        "Sigma" => '\u{03a3}',
+        "Tau" => '\u{03a4}',
+        "Upsilon" => '\u{03a5}',
+        "Phi" => '\u{03a6}',
+        "Chi" => '\u{03a7}',
+        "Psi" => '\u{03a8}',
+        "Omega" => '\u{03a9}',
+        "alpha" => '\u{03b1}',
+        "beta" => '\u{03b2}',
+        "gamma" => '\u{03b3}',
+        "delta" => '\u{03b4}',
+        "epsilon" => '\u{03b5}',
+        "zeta" => '\u{03b6}',
+        "eta" => '\u{03b7}',
+        "theta" => '\u{03b8}',
+        "iota" => '\u{03b9}',
+        "kappa" => '\u{03ba}',
+        "lambda" => '\u{03bb}',
+        "mu" => '\u{03bc}',
+        "nu" => '\u{03bd}',
+        "xi" => '\u{03be}',
+        "omicron" => '\u{03bf}',
+        "pi" => '\u{03c0}',
+        "rho" => '\u{03c1}',
+        "sigmaf" => '\u{03c2}',
+        "sigma" => '\u{03c3}',
+        "tau" => '\u{03c4}',
+        "upsilon" => '\u{03c5}',
+        "phi" => '\u{03c6}',
+        "chi" => '\u{03c7}',
+        "psi" => '\u{03c8}',
+        "omega" => '\u{03c9}',
+        "thetasym" => '\u{03d1}',
+        "upsih" => '\u{03d2}',
+        "piv" => '\u{03d6}',
+        "ensp" => '\u{2002}',
+        "emsp" => '\u{2003}',
+        "thinsp" => '\u{2009}',
+        "zwnj" => '\u{200c}',
+        "zwj" => '\u{200d}',
+        "lrm" => '\u{200e}',
+        "rlm" => '\u{200f}',
+        "ndash" => '\u{2013}',
+        "mdash" => '\u{2014}',
+        "lsquo" => '\u{2018}',
+        "rsquo" => '\u{2019}',
+        "sbquo" => '\u{201a}',
+        "ldquo" => '\u{201c}',
+        "rdquo" => '\u{201d}',
+        "bdquo" => '\u{201e}',
+        "dagger" => '\u{2020}',
+        "Dagger" => '\u{2021}',
+        "bull" => '\u{2022}',
+        "hellip" => '\u{2026}',
+        "permil" => '\u{2030}',
+        "prime" => '\u{2032}',
+        "Prime" => '\u{2033}',
+        "lsaquo" => '\u{2039}',
+        "rsaquo" => '\u{203a}',
+        "oline" => '\u{203e}',
+        "frasl" => '\u{2044}',
+        "euro" => '\u{20ac}',
+        "image" => '\u{2111}',
+        "weierp" => '\u{2118}',
+        "real" => '\u{211c}',
+        "trade" => '\u{2122}',
+        "alefsym" => '\u{2135}',
+        "larr" => '\u{2190}',
+        "uarr" => '\u{2191}',
+        "rarr" => '\u{2192}',
+        "darr" => '\u{2193}',
+        "harr" => '\u{2194}',
+        "crarr" => '\u{21b5}',
+        "lArr" => '\u{21d0}',
+        "uArr" => '\u{21d1}',
+        "rArr" => '\u{21d2}',
+        "dArr" => '\u{21d3}',
+        "hArr" => '\u{21d4}',
+        "forall" => '\u{2200}',
+        "part" => '\u{2202}',
+        "exist" => '\u{2203}',
+        "empty" => '\u{2205}',
+        "nabla" => '\u{2207}',
+        "isin" => '\u{2208}',
+        "notin" => '\u{2209}',
+        "ni" => '\u{220b}',
+        "prod" => '\u{220f}',
+        "sum" => '\u{2211}',
+        "minus" => '\u{2212}',
+        "lowast" => '\u{2217}',
+        "radic" => '\u{221a}',
+        "prop" => '\u{221d}',
+        "infin" => '\u{221e}',
+        "ang" => '\u{2220}',
+        "and" => '\u{2227}',
+        "or" => '\u{2228}',
+        "cap" => '\u{2229}',
+        "cup" => '\u{222a}',
+        "int" => '\u{222b}',
+        "there4" => '\u{2234}',
+        "sim" => '\u{223c}',
+        "cong" => '\u{2245}',
+        "asymp" => '\u{2248}',
+        "ne" => '\u{2260}',
+        "equiv" => '\u{2261}',
+        "le" => '\u{2264}',
+        "ge" => '\u{2265}',
+        "sub" => '\u{2282}',
+        "sup" => '\u{2283}',
+        "nsub" => '\u{2284}',
+        "sube" => '\u{2286}',
+        "supe" => '\u{2287}',
+        "oplus" => '\u{2295}',
+        "otimes" => '\u{2297}',
+        "perp" => '\u{22a5}',
+        "sdot" => '\u{22c5}',
+        "lceil" => '\u{2308}',
+        "rceil" => '\u{2309}',
+        "lfloor" => '\u{230a}',
+        "rfloor" => '\u{230b}',
+        "lang" => '\u{2329}',
+        "rang" => '\u{232a}',
+        "loz" => '\u{25ca}',
+        "spades" => '\u{2660}',
+        "clubs" => '\u{2663}',
+        "hearts" => '\u{2665}',
+        "diams" => '\u{2666}',
+        _ => {
+            let offset = LATIN1_ENTITIES.iter().position(|&entity| entity == name)?;
+            return std::char::from_u32(0xa0 + offset as u32);
+        }
+    };
+    Some(ch)
+}
+
+/// Replace the character references in a run of text or an attribute value.
+///
+/// Unrecognized references are left as literal text, as browsers do.
+fn decode_entities(raw: &str) -> String {
+    let mut decoded = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        match decode_reference(rest) {
+            Some((ch, len)) => {
+                decoded.push(ch);
+                rest = &rest[len..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Decode the character reference at the start of `source`, returning the
+/// referenced character and the length of the reference.
+///
+/// Numeric references may omit the trailing semicolon; named ones may not.
+fn decode_reference(source: &str) -> Option<(char, usize)> {
+    let body = &source[1..];
+    if let Some(numeral) = body.strip_prefix('#') {
+        let (radix, prefix_len) = match numeral.chars().next() {
+            Some('x') | Some('X') => (16, 1),
+            _ => (10, 0),
+        };
+        let digits = &numeral[prefix_len..];
+        let digits = &digits[..digits.find(|c: char| !c.is_digit(radix)).unwrap_or(digits.len())];
+        if digits.is_empty() {
+            return None;
+        }
+        let ch = u32::from_str_radix(digits, radix)
+            .ok()
+            .and_then(std::char::from_u32)
+            .filter(|&ch| ch != '\0')
+            .unwrap_or('\u{fffd}');
+        let len = "&#".len() + prefix_len + digits.len();
+        Some((ch, if source[len..].starts_with(';') { len + 1 } else { len }))
+    } else {
+        let name_len = body.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(body.len());
+        if !body[name_len..].starts_with(';') {
+            return None;
+        }
+        named_entity(&body[..name_len]).map(|ch| (ch, name_len + "&;".len()))
+    }
+}
+
+/// Is this element's content raw text, parsed verbatim up to its closing tag?
+fn is_raw_text_element(tag_name: &str) -> bool {
+    matches!(tag_name, "script" | "style")
 }
 
 pub struct Parser {
     pos: usize,
     input: String,
+    lenient: bool,
+    /// Tag names of the elements enclosing the current position.
+    open_elements: Vec<String>,
 }
 
 impl Parser {
-    /// The only doctype directive recognized by this parser.
-    pub const HTML_DOCTYPE: &'static str = "<!DOCTYPE html>";
-
     /// Create a fresh HTML parser on the given input string.
     pub fn new(input: String) -> Parser {
-        Parser { pos: 0, input }
+        Parser { pos: 0, input, lenient: false, open_elements: Vec::new() }
     }
 
-    /// Parse a whole HTML document.
-    pub fn parse_document(&mut self) -> Vec<DocumentNode> {
-        self.parse_doctype();
-        self.parse_nodes()
+    /// Create a fresh HTML parser that recovers from malformed markup.
+    pub fn new_lenient(input: String) -> Parser {
+        Parser { lenient: true, ..Parser::new(input) }
     }
 
-    /// Parse the opening doctype directive, if present, in order to ignore it.
-    fn parse_doctype(&mut self) {
-        self.consume_whitespace();
-        if self.starts_with(Parser::HTML_DOCTYPE) {
-            for ch in Parser::HTML_DOCTYPE.chars() {
-                assert_eq!(self.consume_char(), ch);
-            }
-        }
+    /// Parse a whole HTML document.
+    pub fn parse_document(&mut self) -> Result<Vec<DocumentNode>, ParseError> {
+        self.parse_nodes()
     }
 
     /// Parse a sequence of sibling nodes.
-    fn parse_nodes(&mut self) -> Vec<DocumentNode> {
-        let mut nodes = vec![];
+    ///
+    /// Stops at the end of input, at a closing tag for any open element, or at
+    /// an opening tag that implicitly closes an open element. Closing tags for
+    /// void elements are ignored; any other closing tag is an error (or
+    /// discarded, in lenient mode). Text separated only by skipped markup is
+    /// merged into one text node.
+    fn parse_nodes(&mut self) -> Result<Vec<DocumentNode>, ParseError> {
+        let mut nodes: Vec<DocumentNode> = vec![];
         loop {
-            self.consume_whitespace();
-            if self.eof() || self.starts_with("</") {
+            if self.eof() {
+                break;
+            }
+            if self.starts_with("<!--") {
+                self.skip_comment()?;
+                continue;
+            }
+            if self.starts_with("<!") || self.starts_with("<?") {
+                self.skip_directive()?;
+                continue;
+            }
+            if self.starts_with("</") {
+                let tag_name = self.peek_closing_tag();
+                if self.open_elements.contains(&tag_name) {
+                    break;
+                }
+                if is_void_element(&tag_name) {
+                    self.skip_closing_tag();
+                    continue;
+                }
+                if !self.lenient {
+                    return Err(self.error(format!("unexpected closing tag `</{}>`", tag_name)));
+                }
+                self.skip_closing_tag();
+                continue;
+            }
+            if self.at_opening_tag() && self.closes_open_element(&self.peek_opening_tag()) {
                 break;
             }
-            nodes.push(self.parse_node());
+            let node = self.parse_node()?;
+            match (nodes.last_mut().map(|last| &mut last.node_type), &node.node_type) {
+                (Some(NodeType::Text(prev)), NodeType::Text(next)) => prev.push_str(next),
+                _ => nodes.push(node),
+            }
         }
-        nodes
+        Ok(nodes)
     }
 
     /// Parse a single node.
-    fn parse_node(&mut self) -> DocumentNode {
-        match self.next_char() {
-            '<' => self.parse_element(),
-            _ => self.parse_text(),
+    fn parse_node(&mut self) -> Result<DocumentNode, ParseError> {
+        if !self.starts_with("<") {
+            Ok(self.parse_text())
+        } else if self.at_opening_tag() {
+            self.parse_element()
+        } else if self.lenient {
+            Ok(self.parse_text())
+        } else {
+            Err(self.error("unexpected `<` outside of a tag".to_string()))
         }
     }
 
     /// Parse a single element, including its open tag, contents, and closing tag.
-    fn parse_element(&mut self) -> DocumentNode {
+    fn parse_element(&mut self) -> Result<DocumentNode, ParseError> {
         // Opening tag.
-        assert_eq!(self.consume_char(), '<');
-        let tag_name = self.parse_identifier();
-        let attr_map = self.parse_attributes();
-        let children = if self.next_char() == '/' {
+        self.expect_char('<')?;
+        let tag_name = self.parse_tag_name();
+        let attr_map = self.parse_attributes()?;
+        if self.starts_with("/>") {
             // Self-closing tag.
-            assert_eq!(self.consume_char(), '/');
-            assert_eq!(self.consume_char(), '>');
-            Vec::new()
+            self.pos += 2;
+            return Ok(DocumentNode::new_elem(tag_name, attr_map, Vec::new()));
+        }
+        self.expect_char('>')?;
+        if is_void_element(&tag_name) {
+            return Ok(DocumentNode::new_elem(tag_name, attr_map, Vec::new()));
+        }
+
+        // Contents.
+        let children = if is_raw_text_element(&tag_name) {
+            self.parse_raw_text(&tag_name).into_iter().collect()
         } else {
-            // Content-enclosing pair of tags.
-            assert_eq!(self.consume_char(), '>');
-            let nodes = self.parse_nodes();
-            assert_eq!(self.consume_char(), '<');
-            assert_eq!(self.consume_char(), '/');
-            assert_eq!(self.parse_identifier(), tag_name);
-            assert_eq!(self.consume_char(), '>');
-            nodes
+            self.open_elements.push(tag_name.clone());
+            let children = self.parse_nodes()?;
+            self.open_elements.pop();
+            children
         };
 
-        DocumentNode::new_elem(tag_name, attr_map, children)
+        // Closing tag, which may be implied by what follows or, when lenient,
+        // missing or belonging to an ancestor.
+        let may_omit = self.lenient || has_optional_end_tag(&tag_name);
+        if self.eof() {
+            if !may_omit {
+                return Err(self.error(format!("unclosed element `<{}>`", tag_name)));
+            }
+        } else if self.starts_with("</") {
+            let closing_name = self.peek_closing_tag();
+            if closing_name == tag_name {
+                self.parse_closing_tag()?;
+            } else if !may_omit {
+                return Err(self.error(format!(
+                    "expected `</{}>` but found `</{}>`",
+                    tag_name, closing_name
+                )));
+            }
+        }
+
+        Ok(DocumentNode::new_elem(tag_name, attr_map, children))
     }
 
-    /// Parse a tag or attribute name.
-    fn parse_identifier(&mut self) -> String {
-        self.consume_while(|c| match c {
-            'a'..='z' | 'A'..='Z' | '0'..='9' => true,
-            _ => false,
-        })
+    /// Skip a `<!-- ... -->` comment.
+    fn skip_comment(&mut self) -> Result<(), ParseError> {
+        self.skip_until("-->", "unterminated comment")
     }
 
-    /// Parse a list of name="value" pairs, separated by whitespace.
-    fn parse_attributes(&mut self) -> AttributeMap {
+    /// Skip a doctype, processing instruction or other `<!...>`/`<?...>`
+    /// directive.
+    fn skip_directive(&mut self) -> Result<(), ParseError> {
+        self.skip_until(">", "unterminated directive")
+    }
+
+    /// Skip past the next occurrence of `terminator`, which is required
+    /// except in lenient mode.
+    fn skip_until(&mut self, terminator: &str, message: &str) -> Result<(), ParseError> {
+        match self.input[self.pos..].find(terminator) {
+            Some(offset) => self.pos += offset + terminator.len(),
+            None if self.lenient => self.pos = self.input.len(),
+            None => return Err(self.error(message.to_string())),
+        }
+        Ok(())
+    }
+
+    /// Parse the contents of a raw text element verbatim, up to its closing
+    /// tag or the end of input.
+    fn parse_raw_text(&mut self, tag_name: &str) -> Option<DocumentNode> {
+        let closing_tag = format!("</{}", tag_name);
+        let start = self.pos;
+        let mut end = start;
+        loop {
+            match self.input[end..].to_ascii_lowercase().find(&closing_tag) {
+                Some(offset) => {
+                    end += offset;
+                    let after = self.input[end + closing_tag.len()..].chars().next();
+                    if !after.map_or(false, valid_tag_name_char) {
+                        break;
+                    }
+                    end += closing_tag.len();
+                }
+                None => {
+                    end = self.input.len();
+                    break;
+                }
+            }
+        }
+        self.pos = end;
+        if end == start {
+            None
+        } else {
+            Some(DocumentNode::new_text(self.input[start..end].to_string()))
+        }
+    }
+
+    /// Parse a closing tag, returning its tag name.
+    fn parse_closing_tag(&mut self) -> Result<String, ParseError> {
+        self.expect_char('<')?;
+        self.expect_char('/')?;
+        let tag_name = self.parse_tag_name();
+        self.consume_whitespace();
+        self.expect_char('>')?;
+        Ok(tag_name)
+    }
+
+    /// Would an opening tag named `tag_name` implicitly close any open element?
+    ///
+    /// Only the innermost open elements up to the first one whose closing tag
+    /// can't be omitted are candidates.
+    fn closes_open_element(&self, tag_name: &str) -> bool {
+        for open in self.open_elements.iter().rev() {
+            if is_implicitly_closed_by(open, tag_name) {
+                return true;
+            }
+            if !has_optional_end_tag(open) {
+                break;
+            }
+        }
+        false
+    }
+
+    /// Read the tag name of the upcoming opening tag without consuming it.
+    fn peek_opening_tag(&self) -> String {
+        self.peek_tag_name("<".len())
+    }
+
+    /// Read the tag name of the upcoming closing tag without consuming it.
+    fn peek_closing_tag(&self) -> String {
+        self.peek_tag_name("</".len())
+    }
+
+    /// Read the tag name starting `offset` bytes ahead without consuming it.
+    fn peek_tag_name(&self, offset: usize) -> String {
+        let view = &self.input[self.pos + offset..];
+        view[..view.find(|c| !valid_tag_name_char(c)).unwrap_or(view.len())].to_ascii_lowercase()
+    }
+
+    /// Discard the upcoming closing tag, however malformed.
+    fn skip_closing_tag(&mut self) {
+        self.consume_while(|c| c != '>');
+        if !self.eof() {
+            self.consume_char();
+        }
+    }
+
+    /// Parse a tag name, lowercased.
+    fn parse_tag_name(&mut self) -> String {
+        self.consume_while(valid_tag_name_char).to_ascii_lowercase()
+    }
+
+    /// Parse an attribute name, lowercased.
+    fn parse_attribute_name(&mut self) -> String {
+        self.consume_while(valid_attribute_name_char).to_ascii_lowercase()
+    }
+
+    /// Parse a list of attributes, separated by whitespace.
+    ///
+    /// As in HTML, the first of any duplicate attributes wins.
+    fn parse_attributes(&mut self) -> Result<AttributeMap, ParseError> {
         let mut attributes = HashMap::new();
         loop {
             self.consume_whitespace();
-            if !self.next_char().is_alphanumeric() {
+            if self.eof() || self.starts_with(">") || self.starts_with("/>") {
                 break;
             }
-            let (name, value) = self.parse_attribute();
-            attributes.insert(name, value);
+            if !valid_attribute_name_char(self.next_char()) {
+                if !self.lenient {
+                    let found = self.next_char();
+                    return Err(self.error(format!("unexpected `{}` in tag", found)));
+                }
+                self.consume_char();
+                continue;
+            }
+            let (name, value) = self.parse_attribute()?;
+            attributes.entry(name).or_insert(value);
         }
-        AttributeMap::new(attributes)
+        Ok(AttributeMap::new(attributes))
     }
 
-    /// Parse a single name="value" pair.
-    fn parse_attribute(&mut self) -> (String, String) {
-        let name = self.parse_identifier();
-        assert_eq!(self.consume_char(), '=');
-        let value = self.parse_quotation();
-        (name, value)
+    /// Parse a single attribute: `name="value"`, `name='value'`, `name=value`
+    /// or just `name` (with an empty value).
+    fn parse_attribute(&mut self) -> Result<(String, String), ParseError> {
+        let name = self.parse_attribute_name();
+        self.consume_whitespace();
+        if !self.starts_with("=") {
+            return Ok((name, String::new()));
+        }
+        self.consume_char();
+        self.consume_whitespace();
+        let value = if self.starts_with("\"") || self.starts_with("'") {
+            self.parse_quotation()?
+        } else {
+            self.consume_while(|c| !c.is_whitespace() && c != '>')
+        };
+        Ok((name, decode_entities(&value)))
     }
 
     /// Parse a quoted value.
-    fn parse_quotation(&mut self) -> String {
+    fn parse_quotation(&mut self) -> Result<String, ParseError> {
         let open_quote = self.consume_char();
-        assert!(open_quote == '"' || open_quote == '\'');
         let value = self.consume_while(|c| c != open_quote);
-        assert_eq!(self.consume_char(), open_quote);
-        value
+        self.expect_char(open_quote)?;
+        Ok(value)
     }
 
-    /// Parse a text node.
+    /// Parse a text node, decoding any character references.
+    ///
+    /// In lenient mode, a `<` that doesn't begin markup is kept as literal text.
     fn parse_text(&mut self) -> DocumentNode {
-        DocumentNode::new_text(self.consume_while(|c| c != '<'))
+        let mut text = self.consume_while(|c| c != '<');
+        while self.lenient && !self.eof() && !self.at_markup() {
+            text.push(self.consume_char());
+            text.push_str(&self.consume_while(|c| c != '<'));
+        }
+        DocumentNode::new_text(decode_entities(&text))
+    }
+
+    /// Does the current input start with an opening tag?
+    fn at_opening_tag(&self) -> bool {
+        let mut iter = self.input[self.pos..].chars();
+        iter.next() == Some('<') && matches!(iter.next(), Some(c) if c.is_ascii_alphabetic())
+    }
+
+    /// Does the current input start with a tag, comment or other directive?
+    fn at_markup(&self) -> bool {
+        self.at_opening_tag() || self.starts_with("</") || self.starts_with("<!") || self.starts_with("<?")
+    }
+
+    /// Consume the expected character, or fail without consuming anything.
+    fn expect_char(&mut self, expected: char) -> Result<(), ParseError> {
+        if self.eof() {
+            return Err(self.error(format!("expected `{}` but found end of input", expected)));
+        }
+        let found = self.next_char();
+        if found != expected {
+            return Err(self.error(format!("expected `{}` but found `{}`", expected, found)));
+        }
+        self.consume_char();
+        Ok(())
+    }
+
+    /// Create a parse error located at the current position.
+    fn error(&self, message: String) -> ParseError {
+        let consumed = &self.input[..self.pos];
+        let line = consumed.matches('\n').count() + 1;
+        let line_start = consumed.rfind('\n').map_or(0, |i| i + 1);
+        let column = consumed[line_start..].chars().count() + 1;
+        ParseError { line, column, message }
     }
 
     /// Consume and discard zero or more whitespace characters.
@@ -161,7 +818,7 @@ impl Parser {
     fn consume_char(&mut self) -> char {
         let mut iter = self.input[self.pos..].char_indices();
         let (_, cur_char) = iter.next().unwrap();
-        let (next_pos, _) = iter.next().unwrap_or((1, ' '));
+        let (next_pos, _) = iter.next().unwrap_or((cur_char.len_utf8(), ' '));
         self.pos += next_pos;
         cur_char
     }
@@ -181,3 +838,176 @@ impl Parser {
         self.pos >= self.input.len()
     }
 }
+
+fn valid_tag_name_char(c: char) -> bool {
+    matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | ':' | '.')
+}
+
+fn valid_attribute_name_char(c: char) -> bool {
+    match c {
+        '"' | '\'' | '<' | '>' | '/' | '=' => false,
+        c => !c.is_whitespace(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom::NodeType;
+
+    /// Outline a node as markup, without attributes or escaping.
+    fn outline(node: &DocumentNode) -> String {
+        match node.node_type {
+            NodeType::Element(ref elem) => {
+                let children: String = node.children.iter().map(outline).collect();
+                format!("<{}>{}</{}>", elem.tag, children, elem.tag)
+            }
+            NodeType::Text(ref text) => text.clone(),
+        }
+    }
+
+    /// Parse (strictly or leniently) and outline an HTML document.
+    fn reparse(source: &str, lenient: bool) -> Result<String, ParseError> {
+        let source = String::from(source);
+        let document = if lenient { parse_document_lenient(source) } else { parse_document(source) };
+        document.map(|document| outline(&document.document_root))
+    }
+
+    #[test]
+    fn parse_errors_are_positioned() {
+        let error = reparse("<html>\n  <p>a</b></html>", false).unwrap_err();
+        assert_eq!((error.line, error.column), (2, 7));
+        assert_eq!(error.to_string(), "2:7: unexpected closing tag `</b>`");
+        let error = reparse("<html><p>a < b</p></html>", false).unwrap_err();
+        assert_eq!((error.line, error.column), (1, 12));
+    }
+
+    #[test]
+    fn lenient_parsing_recovers() {
+        assert_eq!(
+            reparse("<html><div>a</span>b < c<i></div></html>", true).unwrap(),
+            "<html><div>ab < c<i></i></div></html>"
+        );
+        // Not every error is recoverable.
+        assert!(reparse("<html><p class=\"a>x</p></html>", true).is_err());
+    }
+
+    #[test]
+    fn decode_entities_named() {
+        assert_eq!(decode_entities("a &amp; b &lt;c&gt;"), "a & b <c>");
+        assert_eq!(decode_entities("&nbsp;&eacute;&yuml;"), "\u{a0}\u{e9}\u{ff}");
+        assert_eq!(
+            decode_entities("&alpha;&Omega;&sum;&rArr;&diams;"),
+            "\u{3b1}\u{3a9}\u{2211}\u{21d2}\u{2666}"
+        );
+    }
+
+    #[test]
+    fn decode_entities_numeric() {
+        assert_eq!(decode_entities("&#65;&#x42;&#X43;"), "ABC");
+        assert_eq!(decode_entities("&#65 &#0;"), "A \u{fffd}");
+    }
+
+    #[test]
+    fn decode_entities_unrecognized() {
+        assert_eq!(decode_entities("&bogus; &amp &; &#; &"), "&bogus; &amp &; &#; &");
+    }
+
+    #[test]
+    fn skip_comments_and_directives() {
+        assert_eq!(
+            reparse("<!DOCTYPE html><html><!-- <p> --><p>a &amp; <![CDATA[b]]>c</p><?pi?></html>", false)
+                .unwrap(),
+            "<html><p>a & c</p></html>"
+        );
+        // As in HTML, a CDATA section is a bogus comment, ending at the first `>`.
+        assert_eq!(reparse("<html><![CDATA[a>b]]></html>", false).unwrap(), "<html>b]]></html>");
+        assert!(reparse("<html><!-- a </html>", false).is_err());
+        assert_eq!(reparse("<html>a<!-- b </html>", true).unwrap(), "<html>a</html>");
+    }
+
+    #[test]
+    fn raw_text_elements() {
+        assert_eq!(
+            reparse("<html><style>a<b {}</style><script>if (a<b && c>d) x = '</p>';</script></html>", false)
+                .unwrap(),
+            "<html><style>a<b {}</style><script>if (a<b && c>d) x = '</p>';</script></html>"
+        );
+        assert_eq!(
+            reparse("<html><script></scripts></script></html>", false).unwrap(),
+            "<html><script></scripts></script></html>"
+        );
+        assert!(reparse("<html><script>a</html>", false).is_err());
+        assert_eq!(reparse("<html><SCRIPT>a</Script></html>", false).unwrap(), "<html><script>a</script></html>");
+    }
+
+    #[test]
+    fn void_elements_and_optional_end_tags() {
+        assert_eq!(
+            reparse("<html><ul><li>a<li>b</ul><p>x<br>y<img src='i'><p>z</html>", false).unwrap(),
+            "<html><ul><li>a</li><li>b</li></ul><p>x<br></br>y<img></img></p><p>z</p></html>"
+        );
+        // Only optional end tags may be left out.
+        assert!(reparse("<html><div>a</html>", false).is_err());
+    }
+
+    #[test]
+    fn spec_parser_agrees() {
+        let source = "<!DOCTYPE html><html><head><title>t</title></head>\n<body>\
+                      <!-- c --><ul><li>a<li>b &amp; c</ul><p>x<br>y</body></html>";
+        let document = parse_document(String::from(source)).unwrap();
+        let spec_document = parse_document_spec(String::from(source));
+        assert_eq!(outline(&spec_document.document_root), outline(&document.document_root));
+    }
+
+    #[test]
+    fn unquoted_boolean_and_uppercase_attributes() {
+        /// Parse a document and look up attributes on its first element.
+        fn attributes(source: &str, lenient: bool, names: &[&str]) -> Vec<Option<String>> {
+            let source = String::from(source);
+            let document = if lenient { parse_document_lenient(source) } else { parse_document(source) };
+            let root = document.unwrap().document_root;
+            match root.children[0].node_type {
+                NodeType::Element(ref elem) => names
+                    .iter()
+                    .map(|name| elem.attributes.lookup(name).map(String::from))
+                    .collect(),
+                NodeType::Text(_) => panic!("expected an element"),
+            }
+        }
+
+        assert_eq!(
+            reparse("<HTML><Input Type=checkbox CHECKED value='a b'></HTML>", false).unwrap(),
+            "<html><input></input></html>"
+        );
+        assert_eq!(
+            attributes(
+                "<html><Input Type=checkbox CHECKED value='a b' data-x = y\ntype=radio></html>",
+                false,
+                &["type", "checked", "value", "data-x"]
+            ),
+            vec![
+                Some("checkbox".to_string()),
+                Some(String::new()),
+                Some("a b".to_string()),
+                Some("y".to_string())
+            ]
+        );
+        assert!(reparse("<html><p =a>b</p></html>", false).is_err());
+        assert_eq!(
+            attributes("<html><p =a id=b>c</p></html>", true, &["a", "id"]),
+            vec![Some(String::new()), Some("b".to_string())]
+        );
+    }
+
+    #[test]
+    fn serialize_annotated() {
+        let source = "<html><p id=a>x &amp; y<style>a>b{}</style></p><b data-elt=z></b></html>";
+        let document = parse_document(String::from(source)).unwrap();
+        assert_eq!(
+            serialize_document(&document, true),
+            "<html data-elt=\"0\"><p data-elt=\"1\" id=\"a\">x &amp; y\
+             <style data-elt=\"2\">a>b{}</style></p><b data-elt=\"z\"></b></html>"
+        );
+    }
+}
@@ -1,17 +1,20 @@
 #![allow(unused_variables)]
 #![allow(dead_code)]
 
-///! Basic CSS block layout.
-///
-/// N.B.: The version of this file kept under version control is meant as a
-/// "safe" fallback/baseline, omitting more recent improvements to the CSS
-/// attribute grammar. Please don't check in each new auto-generated version,
-/// especially while still debugging.
+//! Basic CSS block layout.
+//!
+//! N.B.: The version of this file kept under version control is meant as a
+//! "safe" fallback/baseline, omitting more recent improvements to the CSS
+//! attribute grammar. Please don't check in each new auto-generated version,
+//! especially while still debugging.
 
 use crate::dom::DocumentNode;
-use crate::style::{StyledTree, StyledNode, Style, DisplayType, Floated, Positioned, Overflow};
+use crate::style::{
+    StyledTree, StyledNode, Style, DisplayType, Floated, Positioned, Overflow, Size, Bound, BoxSizing,
+    WhiteSpace,
+};
 use crate::paint::DisplayList;
-use crate::utility::{Pixels, Edge, Rect, FloatCursor, MarginAccumulator};
+use crate::utility::{Pixels, Length, Automatic::{self, Auto, Given}, Edge, Rect, FloatCursor, MarginAccumulator};
 use crate::lazy::Lazy;
 use std::fmt;
 use itertools::Itertools;
@@ -79,6 +82,9 @@ impl<'a> fmt::Display for LayoutTree<'a> {
 pub struct Layout {
     /// Position and size of the containing block.
     containing_box: Rect<Pixels>,
+    /// Whether the height of the containing block is known before laying out
+    /// its contents, so that percentage heights can resolve against it.
+    definite_height: bool,
     /// Position and size of the positioned containing block.
     positioning_box: Rect<Pixels>,
     /// Position and size of the content box relative to the document origin.
@@ -135,6 +141,11 @@ pub struct Layout {
 /// A node in the layout tree.
 pub struct LayoutNode<'a> {
     document_node: Option<&'a DocumentNode>,
+    /// Whether this box is for (or within) a `::before` or `::after`
+    /// pseudo-element of its document node.
+    generated: bool,
+    /// Text content, for a text run.
+    text: Option<&'a str>,
     /// Specified values from styling.
     style: &'a Style,
     /// Layout state for this node.
@@ -166,6 +177,8 @@ impl<'a> LayoutNode<'a> {
         };
         let generate = |child_nodes| LayoutNode {
             document_node: Some(style_node.node),
+            generated: style_node.is_generated(),
+            text: style_node.as_text(),
             style,
             class,
             children: child_nodes,
@@ -205,7 +218,7 @@ impl<'a> LayoutNode<'a> {
 
             // if class.is_floated() {
             // ==JUFIX== chrome810370: if no children, don't add any anon node
-            if class.is_floated() && style_node.children.len()>0 {
+            if class.is_floated() && !style_node.children.is_empty() {
                 contents = vec![LayoutNode::into_block_root(style, contents)];
             }
 
@@ -258,6 +271,8 @@ impl<'a> LayoutNode<'a> {
     fn anon(wrapper_class: LayoutClass, parent_style: &'a Style, wrapped_nodes: Vec<Self>) -> Self {
         LayoutNode {
             document_node: None,
+            generated: false,
+            text: None,
             style: Box::leak(Box::new(Style::inherit(parent_style))),
             layout: Layout::default(),
             class: wrapper_class,
@@ -304,6 +319,268 @@ impl<'a> LayoutNode<'a> {
     fn is_out_of_flow(&self) -> bool { !self.is_in_flow() }
 
     fn is_anon(&self) -> bool { self.document_node.is_none() }
+
+    /// The block against which this box's size and offsets resolve
+    /// percentages: the positioning box if absolutely positioned (or fixed),
+    /// and otherwise the containing box.
+    fn percentage_basis(&self) -> Rect<Pixels> {
+        if self.is_positioned() {
+            self.layout.positioning_box
+        } else {
+            self.layout.containing_box
+        }
+    }
+
+    /// Does this box resolve percentage heights? Only if the height of its
+    /// containing block doesn't depend on content, as is never the case for
+    /// the positioning box of an absolutely positioned box.
+    fn has_definite_basis_height(&self) -> bool {
+        self.is_positioned() || self.layout.definite_height
+    }
+
+    /// Used (content) width, resolving a percentage of the containing block's
+    /// width, or an intrinsic size keyword.
+    fn width(&self) -> Automatic<Pixels> {
+        let (inset, _) = self.box_sizing_insets();
+        match self.style.width {
+            Size::Length(px) => Given((px - inset).max(0.0)),
+            Size::Percent(pct) => {
+                let width = Length::Percent(pct).resolve(self.percentage_basis().width);
+                Given((width - inset).max(0.0))
+            }
+            Size::MinContent => Given(self.intrinsic_content_widths().0),
+            Size::MaxContent => Given(self.intrinsic_content_widths().1),
+            Size::FitContent => Given(self.fit_content_width()),
+            Size::Fill | Size::Available => Given(self.available_width()),
+            Size::Auto => Auto,
+        }
+    }
+
+    /// Used (content) height, resolving a percentage of the containing block's
+    /// height (or else treating it as `auto` if that height depends on
+    /// content). Intrinsic size keywords also act as `auto` in the block axis.
+    fn height(&self) -> Automatic<Pixels> {
+        let (_, inset) = self.box_sizing_insets();
+        match self.style.height {
+            Size::Length(px) => Given((px - inset).max(0.0)),
+            Size::Percent(pct) if self.has_definite_basis_height() => {
+                let height = Length::Percent(pct).resolve(self.percentage_basis().height);
+                Given((height - inset).max(0.0))
+            }
+            _ => Auto,
+        }
+    }
+
+    /// Total horizontal and vertical breadth of padding and border, which
+    /// explicit widths and heights (and their bounds) include under
+    /// `box-sizing: border-box`, but otherwise exclude.
+    fn box_sizing_insets(&self) -> (Pixels, Pixels) {
+        match self.style.box_sizing {
+            BoxSizing::ContentBox => (0.0, 0.0),
+            BoxSizing::BorderBox => {
+                let (padding, border) = (self.padding(), self.style.border);
+                (
+                    padding.left + padding.right + border.left + border.right,
+                    padding.top + padding.bottom + border.top + border.bottom,
+                )
+            }
+        }
+    }
+
+    /// Used margins, resolving percentages of the containing block's width
+    /// (even vertically).
+    fn margin(&self) -> Edge<Automatic<Pixels>> {
+        let width = self.layout.containing_box.width;
+        self.style.margin.transform(|margin| margin.resolve(width))
+    }
+
+    /// Used padding, resolving percentages of the containing block's width
+    /// (even vertically).
+    fn padding(&self) -> Edge<Pixels> {
+        let width = self.layout.containing_box.width;
+        self.style.padding.transform(|padding| padding.resolve(width))
+    }
+
+    /// Used positioning offsets, resolving percentages of the containing
+    /// block's width (horizontally) or height (vertically).
+    fn offset(&self) -> Edge<Option<Pixels>> {
+        let Rect { width, height, .. } = self.percentage_basis();
+        Edge {
+            left: self.style.left.map(|left| left.resolve(width)),
+            right: self.style.right.map(|right| right.resolve(width)),
+            top: self.style.top.map(|top| top.resolve(height)),
+            bottom: self.style.bottom.map(|bottom| bottom.resolve(height)),
+        }
+    }
+
+    /// Estimate the width available to the children of this box before
+    /// layout, as needed to resolve their percentage margins ahead of it.
+    fn estimate_inner_width(&self) -> Pixels {
+        match (self.class, self.width()) {
+            (LayoutClass::InlineRoot, _) | (LayoutClass::Inline, _) => {
+                self.layout.containing_box.width
+            }
+            (_, Given(width)) => self.clamp_width(width),
+            (LayoutClass::Floated, Auto) | (LayoutClass::InlineBlock, Auto) => {
+                self.clamp_width(self.fit_content_width())
+            }
+            (_, Auto) => self.clamp_width(self.available_width()),
+        }
+    }
+
+    /// Width of the containing block less this box's (non-automatic)
+    /// horizontal margin, border and padding, i.e., the `fill-available` size.
+    fn available_width(&self) -> Pixels {
+        let (margin, padding, border) = (self.margin(), self.padding(), self.style.border);
+        let outer = margin.left.value() + margin.right.value()
+            + padding.left + padding.right
+            + border.left + border.right;
+        (self.layout.containing_box.width - outer).max(0.0)
+    }
+
+    /// The `fit-content` (a.k.a., shrink-to-fit) width, as per
+    /// https://www.w3.org/TR/CSS2/visudet.html#shrink-to-fit-float
+    fn fit_content_width(&self) -> Pixels {
+        let (min_content, max_content) = self.intrinsic_content_widths();
+        max_content.min(min_content.max(self.available_width()))
+    }
+
+    /// Used `min-width` and `max-width` (if any), resolving a percentage or
+    /// an intrinsic size keyword.
+    fn width_bounds(&self) -> (Pixels, Option<Pixels>) {
+        let (inset, _) = self.box_sizing_insets();
+        let bound = |bound| match bound {
+            Bound::Length(px) => Some((px - inset).max(0.0)),
+            Bound::Percent(pct) => {
+                let width = Length::Percent(pct).resolve(self.percentage_basis().width);
+                Some((width - inset).max(0.0))
+            }
+            Bound::MinContent => Some(self.intrinsic_content_widths().0),
+            Bound::MaxContent => Some(self.intrinsic_content_widths().1),
+            Bound::FitContent => Some(self.fit_content_width()),
+            Bound::FillAvailable => Some(self.available_width()),
+            Bound::Auto => None,
+        };
+        (bound(self.style.min_width).unwrap_or(0.0), bound(self.style.max_width))
+    }
+
+    /// Used `min-height` and `max-height` (if any), resolving a percentage of
+    /// the containing block's height, if definite. Otherwise, as for
+    /// `height`, intrinsic size keywords act as no constraint at all.
+    fn height_bounds(&self) -> (Pixels, Option<Pixels>) {
+        let (_, inset) = self.box_sizing_insets();
+        let bound = |bound| match bound {
+            Bound::Length(px) => Some((px - inset).max(0.0)),
+            Bound::Percent(pct) if self.has_definite_basis_height() => {
+                let height = Length::Percent(pct).resolve(self.percentage_basis().height);
+                Some((height - inset).max(0.0))
+            }
+            _ => None,
+        };
+        (bound(self.style.min_height).unwrap_or(0.0), bound(self.style.max_height))
+    }
+
+    /// Constrain a tentative width by `max-width` and then `min-width`.
+    fn clamp_width(&self, width: Pixels) -> Pixels {
+        let (min, max) = self.width_bounds();
+        max.map_or(width, |max| width.min(max)).max(min)
+    }
+
+    /// Constrain a tentative height by `max-height` and then `min-height`, as
+    /// per https://www.w3.org/TR/CSS2/visudet.html#min-max-heights
+    fn clamp_height(&self, height: Pixels) -> Pixels {
+        let (min, max) = self.height_bounds();
+        max.map_or(height, |max| height.min(max)).max(min)
+    }
+
+    /// Used (content) height known before laying out the children, i.e., a
+    /// given `height` constrained by `min-height` and `max-height`, so that
+    /// percentage heights within resolve against the height this box gets.
+    fn clamped_height(&self) -> Pixels {
+        match self.height() {
+            Given(height) => self.clamp_height(height),
+            Auto => 0.0,
+        }
+    }
+}
+
+/// Intrinsic sizing, as per https://www.w3.org/TR/css-sizing-3/#intrinsic-sizes
+///
+/// Without any font metrics, the intrinsic widths of text runs are estimated
+/// from their character counts.
+impl<'a> LayoutNode<'a> {
+    /// The min-content and max-content widths of this box's content, i.e.,
+    /// the narrowest it can be without overflow when taking all soft wrap
+    /// opportunities, and the widest it would be when taking none.
+    fn intrinsic_content_widths(&self) -> (Pixels, Pixels) {
+        let wraps = self.is_inline_container();
+        let (mut min_content, mut max_content) = (0.0f32, 0.0f32);
+        // Inline-level boxes, and adjacent floats, can share a line.
+        let mut line = 0.0f32;
+        for child in &self.children {
+            if child.is_positioned() {
+                continue;
+            }
+            let (min, max) = child.intrinsic_contributions();
+            min_content = min_content.max(min);
+            if wraps || child.is_floated() {
+                line += max;
+                max_content = max_content.max(line);
+            } else {
+                line = 0.0;
+                max_content = max_content.max(max);
+            }
+        }
+        (min_content, max_content)
+    }
+
+    /// The min-content and max-content contributions of this box to its
+    /// container's intrinsic widths, i.e., its outer intrinsic widths.
+    /// Percentages resolve as `auto` or zero, since they depend on the
+    /// container's width in turn.
+    fn intrinsic_contributions(&self) -> (Pixels, Pixels) {
+        let margin = self.style.margin.transform(|margin| margin.resolve(0.0).value());
+        let padding = self.style.padding.transform(|padding| padding.resolve(0.0));
+        let border = self.style.border;
+        let inset = padding.left + padding.right + border.left + border.right;
+        let (min, max) = match (self.class, self.style.width) {
+            (LayoutClass::Text, _) => self.intrinsic_text_widths(),
+            (LayoutClass::Inline, _) | (LayoutClass::InlineRoot, _) => {
+                self.intrinsic_content_widths()
+            }
+            (_, Size::Length(px)) if self.style.box_sizing == BoxSizing::BorderBox => {
+                ((px - inset).max(0.0), (px - inset).max(0.0))
+            }
+            (_, Size::Length(px)) => (px, px),
+            (_, Size::MinContent) => {
+                let (min_content, _) = self.intrinsic_content_widths();
+                (min_content, min_content)
+            }
+            (_, Size::MaxContent) => {
+                let (_, max_content) = self.intrinsic_content_widths();
+                (max_content, max_content)
+            }
+            _ => self.intrinsic_content_widths(),
+        };
+        let outer = margin.left + margin.right + inset;
+        (min + outer, max + outer)
+    }
+
+    /// The estimated min-content and max-content widths of a text run, i.e.,
+    /// those of its longest word and of all its words on one line (unless it
+    /// can't wrap). Every character is taken to be 0.5em wide, as for `ch`
+    /// units in `style::Context::resolve`.
+    fn intrinsic_text_widths(&self) -> (Pixels, Pixels) {
+        let width = |chars: usize| chars as Pixels * self.style.font_size / 2.0;
+        let text = self.text.unwrap_or("");
+        let words: Vec<_> = text.split_whitespace().map(|word| word.chars().count()).collect();
+        let max_content = width(words.iter().sum::<usize>() + words.len().saturating_sub(1));
+        let min_content = match self.style.white_space {
+            WhiteSpace::Pre | WhiteSpace::Nowrap => max_content,
+            _ => width(words.iter().copied().max().unwrap_or(0)),
+        };
+        (min_content, max_content)
+    }
 }
 
 impl<'a> fmt::Display for LayoutNode<'a> {
@@ -317,8 +594,11 @@ impl<'a> fmt::Display for LayoutNode<'a> {
             self.layout.border_box.width,
             self.layout.border_box.height
         );
-        let elem = self.document_node.map(|doc_node| doc_node.index);
-        let text = self.document_node.and_then(DocumentNode::as_text);
+        // Generated boxes have no element of their own to reference.
+        let elem = match self.document_node.and_then(|doc_node| doc_node.element_index) {
+            Some(elem) if !self.generated => format!(" :elt {}", elem),
+            _ => String::new(),
+        };
         let header = match self.class {
 
             // ==JUFIX==: raw fix for automatically created line box
@@ -330,15 +610,15 @@ impl<'a> fmt::Display for LayoutNode<'a> {
                 String::from("[ANON]"),
             
             Text =>
-                format!("[TEXT {} :text \"{}\"]", geometry, text.unwrap()),
+                format!("[TEXT {} :text \"{}\"]", geometry, self.text.unwrap()),
             Line =>
                 String::from("[LINE]"),
             Inline | InlineRoot =>
-                format!("[INLINE :elt {}]", elem.unwrap()),
+                format!("[INLINE{}]", elem),
             InlineBlock =>
-                format!("[INLINE {} :elt {}]", geometry, elem.unwrap()),
+                format!("[INLINE {}{}]", geometry, elem),
             BlockRoot | Block | Floated =>
-                format!("[BLOCK {} :elt {}]", geometry, elem.unwrap()),
+                format!("[BLOCK {}{}]", geometry, elem),
         };
 
         f.write_str("(")?;
@@ -367,7 +647,7 @@ impl LayoutClass {
     fn of_style_node(style_node: &StyledNode) -> Option<Self> {
         if style_node.as_text().is_some() {
             Some(LayoutClass::Text)
-        } else if style_node.node.tag() == Some("html") {
+        } else if style_node.node.tag() == Some("html") && !style_node.is_generated() {
             Some(LayoutClass::BlockRoot)
         } else {
             let style = &style_node.specified;
@@ -465,18 +745,12 @@ impl LayoutClass {
 
     // Is this class of node a floated box?
     fn is_floated(&self) -> bool {
-        match self {
-            LayoutClass::Floated => true,
-            _ => false
-        }
+        matches!(self, LayoutClass::Floated)
     }
 
     // Is this class of node a text run?
     fn is_text_run(&self) -> bool {
-        match self {
-            LayoutClass::Text => true,
-            _ => false
-        }
+        matches!(self, LayoutClass::Text)
     }
 }
 
@@ -484,8 +758,9 @@ impl<'a> LayoutTree<'a> {
     fn layout(&mut self) {
         let width = self.parameters.viewport_width as Pixels;
         let height = self.parameters.viewport_height as Pixels;
-        let block = Rect { x: 0.0, y: 0.0, width: width, height: height };
+        let block = Rect { x: 0.0, y: 0.0, width, height };
         self.layout_root.layout.containing_box = block;
+        self.layout_root.layout.definite_height = true;
         self.layout_root.layout.positioning_box = block;
         self.layout_root.layout.ns_positioning_box = block;
         self.layout_root.layout.init_positioning_box = block;
@@ -523,10 +798,11 @@ impl<'a> LayoutNode<'a> {
     fn compute_effective_margin(&mut self) {
         // println!("float: {}",self.is_floated());
         // println!("sc status: {}",self.layout.self_collapse);
-        self.layout.margin.top = self.style.margin.top.value();
-        self.layout.margin.bottom = self.style.margin.bottom.value();
-        self.layout.margin.left = self.style.margin.left.value();
-        self.layout.margin.right = self.style.margin.right.value();
+        let margin = self.margin();
+        self.layout.margin.top = margin.top.value();
+        self.layout.margin.bottom = margin.bottom.value();
+        self.layout.margin.left = margin.left.value();
+        self.layout.margin.right = margin.right.value();
 
         self.layout.effective_margin.left = self.layout.margin.left;
         self.layout.effective_margin.right = self.layout.margin.right;
@@ -537,9 +813,11 @@ impl<'a> LayoutNode<'a> {
         // println!("====");
 
         // compute sibling collapse in children
+        let inner_width = self.estimate_inner_width();
         let mut prev_child_in_flow: Option<&mut LayoutNode> = None;
         for child in &mut self.children {
 
+            child.layout.containing_box.width = inner_width;
             child.compute_effective_margin();
             // till here, all effective_margin fields are available
 
@@ -582,7 +860,7 @@ impl<'a> LayoutNode<'a> {
         else {
 
             // top margin collapse with first in-flow child
-            if self.style.border.top==0.0 && self.style.padding.top==0.0 {
+            if self.style.border.top==0.0 && self.padding().top==0.0 {
                 // can do top margin collapse
 
                 let mut has_first_child = false;
@@ -626,7 +904,7 @@ impl<'a> LayoutNode<'a> {
             }
 
             // bottom margin collapse with last in-flow child
-            if self.style.border.bottom==0.0 && self.style.padding.top==0.0 {
+            if self.style.border.bottom==0.0 && self.padding().top==0.0 {
                 // can do bottom margin collapse
 
                 let mut has_last_child = false;
@@ -693,7 +971,7 @@ impl<'a> LayoutNode<'a> {
 
         // Position the box below all the previous boxes in the container.
         self.layout.content_box.y = self.layout.block_pos;
-        self.layout.content_box.height = self.style.height.value();
+        self.layout.content_box.height = self.clamped_height();
 
         // println!("(bf) ir float cursor left: {}",self.layout.float_cursor.left_block_end);
         // println!("(bf) ir float cursor block start: {}",self.layout.float_cursor.block_start);
@@ -726,10 +1004,12 @@ impl<'a> LayoutNode<'a> {
         }
 
         let mut inline_cursor = inline_start;
+        let definite_height = self.height().is_given();
         for child in &mut self.children {
             // println!("ir inline cursor: {}",inline_cursor);
             // Give the child box the boundaries of its container.
             child.layout.containing_box = self.layout.content_box;
+            child.layout.definite_height = definite_height;
             child.layout.positioning_box = self.layout.positioning_box;
             child.layout.block_pos = block_cursor;
             child.layout.inline_pos = inline_cursor;
@@ -771,11 +1051,12 @@ impl<'a> LayoutNode<'a> {
 
         // Parent height can depend on child height, so `calculate_height` must be called after the
         // children are laid out.
-        self.layout.content_box.height = if self.style.height.is_auto() {
+        let content_height = if self.height().is_auto() {
             block_cursor - self.layout.content_box.y
         } else {
-            self.style.height.value()
+            self.height().value()
         };
+        self.layout.content_box.height = self.clamp_height(content_height);
 
         self.layout.padding_box = self.layout.content_box.extend_by(&self.layout.padding);
         self.layout.border_box = self.layout.padding_box.extend_by(&self.layout.border);
@@ -787,7 +1068,7 @@ impl<'a> LayoutNode<'a> {
     /// Lay out a block-level element and its descendants.
     fn layout_inline(&mut self) {
         println!("call layout_inline");
-        self.layout.padding = self.style.padding;
+        self.layout.padding = self.padding();
         self.layout.border = self.style.border;
 
         // Position the box flush left (w.r.t. margin/border/padding) to the containing block.
@@ -809,12 +1090,13 @@ impl<'a> LayoutNode<'a> {
         let block_cursor = self.layout.content_box.y;
         let mut block_size = 0.0f32;
         let mut inline_cursor = self.layout.content_box.x;
-        let mut inline_size = 0.0f32;
+        let definite_height = self.height().is_given();
         for child in &mut self.children {
             // println!("computed block_cursor: {}",block_cursor);
             // println!("computed inline_cursor: {}",inline_cursor);
             // Give the child box the boundaries of its container.
             child.layout.containing_box = self.layout.content_box;
+            child.layout.definite_height = definite_height;
             child.layout.positioning_box = self.layout.positioning_box;
             child.layout.block_pos = block_cursor;
             child.layout.inline_pos = inline_cursor;
@@ -825,8 +1107,7 @@ impl<'a> LayoutNode<'a> {
             // Increment the cursor so each child is laid out below the previous one.
             if !child.is_positioned() {
                 // println!("increment");
-                inline_cursor = inline_cursor + child.layout.inline_size;
-                inline_size = inline_size + child.layout.inline_size;
+                inline_cursor += child.layout.inline_size;
                 block_size = block_size.max(child.layout.block_size);
             }
 
@@ -836,10 +1117,10 @@ impl<'a> LayoutNode<'a> {
 
         // Parent height can depend on child height, so `calculate_height` must be called after the
         // children are laid out.
-        self.layout.content_box.height = if self.style.height.is_auto() {
+        self.layout.content_box.height = if self.height().is_auto() {
             block_cursor - self.layout.content_box.y
         } else {
-            self.style.height.value()
+            self.height().value()
         };
 
         self.layout.padding_box = self.layout.content_box.extend_by(&self.layout.padding);
@@ -852,7 +1133,7 @@ impl<'a> LayoutNode<'a> {
     /// Lay out a block-level element and its descendants.
     fn layout_block(&mut self) {
         println!("call layout_block");
-        self.layout.padding = self.style.padding;
+        self.layout.padding = self.padding();
         self.layout.border = self.style.border;
 
         // Child width can depend on parent width, so we need to calculate this box's width before
@@ -883,8 +1164,11 @@ impl<'a> LayoutNode<'a> {
         // println!("block float cursor left: {}",self.layout.float_cursor.left_block_end);
 
         if self.style.overflow != Overflow::Visible || self.class==LayoutClass::InlineBlock {
-            self.layout.content_box.width = if self.style.width.is_auto() {
+            let shrink_to_fit = self.class==LayoutClass::InlineBlock;
+            let content_width = if self.width().is_auto() && shrink_to_fit {
                 // min(max(preferred_minimum_width, available_width), preferred_width)
+                self.fit_content_width()
+            } else if self.width().is_auto() {
                 self.layout.containing_box.width
                 - self.layout.padding.left
                 - self.layout.padding.right
@@ -895,11 +1179,12 @@ impl<'a> LayoutNode<'a> {
                 // - self.layout.margin.left
                 // - self.layout.margin.right
             } else {
-                self.style.width.value()
+                self.width().value()
             };
-            self.layout.content_box.height = self.style.height.value();
+            self.layout.content_box.width = self.clamp_width(content_width);
+            self.layout.content_box.height = self.clamped_height();
 
-            let mut available = self.layout.containing_box.clone();
+            let mut available = self.layout.containing_box;
             available.y = self.layout.block_pos;
             let outer_width =
                 self.layout.content_box.width
@@ -972,11 +1257,11 @@ impl<'a> LayoutNode<'a> {
         
         
 
-        self.layout.content_box.height = self.style.height.value();
+        self.layout.content_box.height = self.clamped_height();
 
         if self.is_positioned() {
             // println!("start is_positioned");
-            if let Some(abs_left) = self.style.left {
+            if let Some(abs_left) = self.offset().left {
                 // println!("b1");
                 self.layout.content_box.x =
                     self.layout.positioning_box.x
@@ -984,7 +1269,7 @@ impl<'a> LayoutNode<'a> {
                     + self.layout.padding.left
                     + self.layout.border.left
                     + self.layout.margin.left;
-            } else if let Some(abs_right) = self.style.right {
+            } else if let Some(abs_right) = self.offset().right {
                 self.layout.content_box.x =
                     self.layout.positioning_box.x
                     + self.layout.positioning_box.width
@@ -995,12 +1280,12 @@ impl<'a> LayoutNode<'a> {
             // else {
             //     self.layout.content_box.width = 0.0;
             // }
-            if let Some(abs_top) = self.style.top {
+            if let Some(abs_top) = self.offset().top {
                 // println!("b1");
                 self.layout.content_box.y =
                     self.layout.positioning_box.y
                     + abs_top;
-            } else if let Some(abs_bottom) = self.style.bottom {
+            } else if let Some(abs_bottom) = self.offset().bottom {
                 self.layout.content_box.y =
                     self.layout.positioning_box.y
                     + self.layout.positioning_box.height
@@ -1014,10 +1299,10 @@ impl<'a> LayoutNode<'a> {
         }
 
         if self.is_relative() {
-            if let Some(dx) = self.style.left {
+            if let Some(dx) = self.offset().left {
                 self.layout.content_box.x += dx;
             }
-            if let Some(dy) = self.style.top {
+            if let Some(dy) = self.offset().top {
                 self.layout.content_box.y += dy;
             }
         }
@@ -1026,10 +1311,12 @@ impl<'a> LayoutNode<'a> {
         // Recursively lay out the children of this box.
         let mut block_cursor = self.layout.content_box.y;
         let pre_border_box = self.layout.content_box.extend_by(&self.layout.padding).extend_by(&self.layout.border);
+        let definite_height = self.height().is_given();
         for child in &mut self.children {
             // println!("??? child float: {}",child.is_floated());
             // Give the child box the boundaries of its container.
             child.layout.containing_box = self.layout.content_box;
+            child.layout.definite_height = definite_height;
             // child.layout.positioning_box = if self.style.position == Positioned::Static {
             //     self.layout.positioning_box
             // } else {
@@ -1084,23 +1371,26 @@ impl<'a> LayoutNode<'a> {
         }
 
         // ==JUFIX== QuickFix
-        if !self.style.height.is_auto() {
-            if self.style.overflow == Overflow::Hidden || self.style.overflow == Overflow::Scroll || self.style.overflow == Overflow::Auto {
-                self.layout.block_extent = self.style.height.value();
-            }
+        if !self.height().is_auto()
+            && (self.style.overflow == Overflow::Hidden
+                || self.style.overflow == Overflow::Scroll
+                || self.style.overflow == Overflow::Auto)
+        {
+            self.layout.block_extent = self.clamp_height(self.height().value());
         }
 
         // Parent height can depend on child height, so `calculate_height` must be called after the
         // children are laid out.
-        self.layout.content_box.height = if self.style.height.is_auto() {
+        let content_height = if self.height().is_auto() {
             if self.is_block_root() {
                 block_cursor.max(self.layout.block_extent) - self.layout.content_box.y
             } else {
                 block_cursor - self.layout.content_box.y
             }
         } else {
-            self.style.height.value()
+            self.height().value()
         };
+        self.layout.content_box.height = self.clamp_height(content_height);
         // println!("====");
         // println!("self.layout.content_box.y: {}",self.layout.content_box.y);
         // println!("computed block_cursor: {}",block_cursor);
@@ -1140,7 +1430,7 @@ impl<'a> LayoutNode<'a> {
         else {
             // ==JUFIX== QuickFix
             if self.style.position == Positioned::Relative {
-                if let Some(rel_top) = self.style.top {
+                if let Some(rel_top) = self.offset().top {
                     self.layout.block_extent = self.layout.block_extent.max(
                         // self.layout.margin_box.y + self.layout.margin_box.height
                         self.layout.border_box.y + self.layout.border_box.height
@@ -1181,6 +1471,25 @@ impl<'a> LayoutNode<'a> {
     ///
     /// Sets the horizontal margin/padding/border dimensions, and the `width`.
     fn calculate_block_width(&mut self) {
+        // Resolve the width as specified, and then again if the result violates
+        // `max-width` or `min-width`, as per
+        // https://www.w3.org/TR/CSS2/visudet.html#min-max-widths
+        self.solve_block_width(self.width());
+        let width = self.layout.content_box.width;
+        if self.clamp_width(width) != width {
+            self.solve_block_width(Given(self.clamp_width(width)));
+        }
+
+        // Horizontal margins never collapse, but any automatic ones are only
+        // resolved now (to center the box, say).
+        if self.is_block_flow() && !self.is_positioned() {
+            self.layout.effective_margin.left = self.layout.margin.left;
+        }
+    }
+
+    /// Solve the horizontal constraint equation for a block-level box of the
+    /// given width (or automatic width).
+    fn solve_block_width(&mut self, width: Automatic<Pixels>) {
 
         // println!("====");
         // println!("self.style.width.is_auto: {}", self.style.width.is_auto());
@@ -1188,16 +1497,18 @@ impl<'a> LayoutNode<'a> {
         // println!("self.style.margin.right.is_auto: {}", self.style.margin.right.is_auto());
         // println!("====");
         
+        let (margin, padding) = (self.margin(), self.padding());
+
         // Adjust used values to balance this difference, by increasing the total width by exactly
         // `underflow` pixels.
         self.layout.underflow = self.layout.containing_box.width - [
-            self.style.margin.left.value(), self.style.margin.right.value(),
+            margin.left.value(), margin.right.value(),
             self.style.border.left, self.style.border.right,
-            self.style.padding.left, self.style.padding.right,
-            self.style.width.value(),
+            padding.left, padding.right,
+            width.value(),
         ].iter().sum::<f32>();
 
-        self.layout.content_box.width = if self.style.width.is_auto() {
+        self.layout.content_box.width = if width.is_auto() {
             if self.style.position == Positioned::Fixed {
                 0.0
             }
@@ -1205,7 +1516,7 @@ impl<'a> LayoutNode<'a> {
                 self.layout.underflow.max(0.0)
             }
         } else {
-            self.style.width.value()
+            width.value()
         };
         
         // Adjust used values to balance this difference, by increasing the total width by exactly
@@ -1223,32 +1534,32 @@ impl<'a> LayoutNode<'a> {
         //     self.style.width.value()
         // };
 
-        self.layout.margin.left = if self.style.margin.left.is_auto() {
-            if self.style.width.is_auto() || self.layout.underflow < 0.0 {
+        self.layout.margin.left = if margin.left.is_auto() {
+            if width.is_auto() || self.layout.underflow < 0.0 {
                 0.0
-            } else if self.style.margin.right.is_auto() {
+            } else if margin.right.is_auto() {
                 self.layout.underflow / 2.0
             } else {
                 self.layout.underflow
             }
         } else {
-            self.style.margin.left.value()
+            margin.left.value()
         };
 
-        self.layout.margin.right = if self.style.width.is_auto() && self.layout.underflow < 0.0 {
-            self.style.margin.right.value() + self.layout.underflow
-        } else if self.style.margin.right.is_auto() {
-            if self.style.width.is_auto() {
+        self.layout.margin.right = if width.is_auto() && self.layout.underflow < 0.0 {
+            margin.right.value() + self.layout.underflow
+        } else if margin.right.is_auto() {
+            if width.is_auto() {
                 0.0
-            } else if self.style.margin.left.is_auto() {
+            } else if margin.left.is_auto() {
                 self.layout.underflow / 2.0
             } else {
                 self.layout.underflow
             }
-        } else if !self.style.margin.left.is_auto() || !self.style.width.is_auto() {
-            self.style.margin.right.value() + self.layout.underflow
+        } else if !margin.left.is_auto() || !width.is_auto() {
+            margin.right.value() + self.layout.underflow
         } else {
-            self.style.margin.right.value()
+            margin.right.value()
         };
     }
 
@@ -1256,7 +1567,7 @@ impl<'a> LayoutNode<'a> {
     fn layout_float(&mut self) {
         // println!("====");
         println!("call layout_float");
-        self.layout.padding = self.style.padding;
+        self.layout.padding = self.padding();
         self.layout.border = self.style.border;
 
         if self.style.clear.left {
@@ -1270,23 +1581,16 @@ impl<'a> LayoutNode<'a> {
             );
         }
 
-        self.layout.content_box.width = if self.style.width.is_auto() {
+        let content_width = if self.width().is_auto() {
             // min(max(preferred_minimum_width, available_width), preferred_width)
-            self.layout.containing_box.width
-            - self.layout.padding.left
-            - self.layout.padding.right
-            - self.layout.border.left
-            - self.layout.border.right
-            - self.layout.effective_margin.left
-            - self.layout.effective_margin.right
-            // - self.layout.margin.left
-            // - self.layout.margin.right
+            self.fit_content_width()
         } else {
-            self.style.width.value()
+            self.width().value()
         };
-        self.layout.content_box.height = self.style.height.value();
+        self.layout.content_box.width = self.clamp_width(content_width);
+        self.layout.content_box.height = self.clamped_height();
 
-        let mut available = self.layout.containing_box.clone();
+        let mut available = self.layout.containing_box;
         available.y = self.layout.block_pos;
         let outer_width =
             self.layout.content_box.width
@@ -1344,9 +1648,11 @@ impl<'a> LayoutNode<'a> {
         let inline_cursor = self.layout.content_box.x;
         let mut block_cursor = self.layout.content_box.y;
         let mut inner_float_cursor = FloatCursor::empty();
+        let definite_height = self.height().is_given();
         for child in &mut self.children {
             // Give the child box the boundaries of its container.
             child.layout.containing_box = self.layout.content_box;
+            child.layout.definite_height = definite_height;
             child.layout.positioning_box = if self.style.position == Positioned::Static {
                 self.layout.positioning_box
             } else {
@@ -1368,15 +1674,16 @@ impl<'a> LayoutNode<'a> {
 
         // Parent height can depend on child height, so `calculate_height` must be called after the
         // children are laid out.
-        self.layout.content_box.height = if self.style.height.is_auto() {
+        let content_height = if self.height().is_auto() {
             if self.is_block_root() {
                 block_cursor.max(self.layout.block_extent) - self.layout.content_box.y
             } else {
                 block_cursor - self.layout.content_box.y
             }
         } else {
-            self.style.height.value()
+            self.height().value()
         };
+        self.layout.content_box.height = self.clamp_height(content_height);
         // println!("float self.layout.content_box.height: {}",self.layout.content_box.height);
 
         self.layout.padding_box = self.layout.content_box.extend_by(&self.layout.padding);
@@ -1434,3 +1741,95 @@ impl<'a> LayoutNode<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom::ElementData;
+    use crate::{css, html, style};
+
+    const PARAMETERS: Parameters =
+        Parameters { viewport_width: 1280, viewport_height: 720, scrollbar_width: 0, font_size: 16 };
+
+    /// Build the layout tree (before any layout) of a styled document, and
+    /// pass the box of the element with the given ID to `test`.
+    fn with_box(source: &str, css: &str, id: &str, test: impl FnOnce(&LayoutNode)) {
+        let document = html::parse_document(String::from(source)).unwrap();
+        let stylesheet = css::parse(String::from(css));
+        let style_tree = style::style_tree(&document, None, &stylesheet, PARAMETERS);
+        let root = LayoutNode::new(&style_tree.style_root);
+        test(find(&root, id).expect(id));
+    }
+
+    fn find<'a, 'b>(node: &'b LayoutNode<'a>, id: &str) -> Option<&'b LayoutNode<'a>> {
+        let elem = node.document_node.and_then(DocumentNode::as_elem);
+        if !node.generated && elem.and_then(ElementData::id) == Some(id) {
+            return Some(node);
+        }
+        node.children.iter().find_map(|child| find(child, id))
+    }
+
+    #[test]
+    fn intrinsic_widths_of_floats_and_blocks() {
+        let css = ".a { width: 50px; height: 10px; } .b { width: 30px; height: 10px; } \
+                   #f div { float: left; }";
+        let source = "<html><body><div id=f><div class=a></div><div class=b></div></div>\
+                      <div id=g><div class=a></div><div class=b></div></div></body></html>";
+        // Floats can share a line, whereas blocks can't.
+        with_box(source, css, "f", |f| assert_eq!(f.intrinsic_content_widths(), (50.0, 80.0)));
+        with_box(source, css, "g", |g| assert_eq!(g.intrinsic_content_widths(), (50.0, 50.0)));
+    }
+
+    #[test]
+    fn intrinsic_widths_of_text() {
+        let source = "<html><body><p id=p>hello big world</p><pre id=q>hello big</pre></body></html>";
+        let css = "p, pre { font-size: 20px; } pre { white-space: pre; }";
+        with_box(source, css, "p", |p| assert_eq!(p.intrinsic_content_widths(), (50.0, 150.0)));
+        with_box(source, css, "q", |q| assert_eq!(q.intrinsic_content_widths(), (90.0, 90.0)));
+    }
+
+    #[test]
+    fn intrinsic_contributions_include_edges() {
+        let source = "<html><body><div id=d></div></body></html>";
+        let css = "#d { width: 40px; padding: 0 5px; border: 1px solid; margin: 0 2px 0 auto; }";
+        with_box(source, css, "d", |d| assert_eq!(d.intrinsic_contributions(), (54.0, 54.0)));
+    }
+
+    #[test]
+    fn clamp_to_bounds() {
+        let source = "<html><body><div id=d></div><div id=e></div></body></html>";
+        let css = "#d { min-width: 100px; max-width: 200px; min-height: 10px; max-height: 20%; } \
+                   #e { min-width: 300px; max-width: 200px; min-height: 30px; max-height: 20px; }";
+        with_box(source, css, "d", |d| {
+            assert_eq!(d.clamp_width(50.0), 100.0);
+            assert_eq!(d.clamp_width(150.0), 150.0);
+            assert_eq!(d.clamp_width(300.0), 200.0);
+            assert_eq!(d.clamp_height(5.0), 10.0);
+            // A percentage of an indefinite height is no constraint.
+            assert_eq!(d.clamp_height(500.0), 500.0);
+        });
+        // `min-width` and `min-height` win over smaller maximums.
+        with_box(source, css, "e", |e| {
+            assert_eq!(e.clamp_width(250.0), 300.0);
+            assert_eq!(e.clamp_height(25.0), 30.0);
+        });
+    }
+
+    #[test]
+    fn border_box_sizes_exclude_padding_and_border() {
+        let source = "<html><body><div id=d></div><div id=e></div></body></html>";
+        let css = "div { width: 100px; height: 50px; min-width: 90px; padding: 10px; \
+                   border: 5px solid; } #d { box-sizing: border-box; } \
+                   #e { box-sizing: content-box; }";
+        with_box(source, css, "d", |d| {
+            assert_eq!(d.width(), Given(70.0));
+            assert_eq!(d.height(), Given(20.0));
+            assert_eq!(d.clamp_width(0.0), 60.0);
+        });
+        with_box(source, css, "e", |e| {
+            assert_eq!(e.width(), Given(100.0));
+            assert_eq!(e.height(), Given(50.0));
+            assert_eq!(e.clamp_width(0.0), 90.0);
+        });
+    }
+}
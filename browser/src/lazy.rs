@@ -25,9 +25,9 @@ use std::rc::Rc;
 /// constraints cannot statically model the necessary pattern of value sharing.
 /// More or less, `Lazy<T>` is the fusion of `Cow<T>` (copy-on-write sharing)
 /// and `Rc<T>` (dynamic lifetimes via reference counting).
-pub struct Lazy<T: ?Sized + Default + Clone>(UnsafeCell<Option<Rc<T>>>);
+pub struct Lazy<T: Default + Clone>(UnsafeCell<Option<Rc<T>>>);
 
-impl<T: ?Sized + Default + Clone> Lazy<T> {
+impl<T: Default + Clone> Lazy<T> {
     /// Create a new lazy pointer eagerly initialized with `val`.
     pub fn new(val: T) -> Self {
         Lazy(UnsafeCell::new(Some(Rc::new(val))))
@@ -84,6 +84,7 @@ impl<T: ?Sized + Default + Clone> Lazy<T> {
     /// initialization entails heap allocation (plus default initialization).
     ///
     /// Same as `AsRef::as_ref()`, `Deref::deref()`, and `Borrow::borrow()`.
+    #[allow(clippy::should_implement_trait)]
     pub fn as_ref(this: &Self) -> &T {
         unsafe { Rc::deref(this.ptr()) }
     }
@@ -102,6 +103,7 @@ impl<T: ?Sized + Default + Clone> Lazy<T> {
     /// initialization.
     ///
     /// Same as `AsMut::as_mut()`.
+    #[allow(clippy::should_implement_trait)]
     pub fn as_mut(this: &mut Self) -> &mut T {
         unsafe { Rc::make_mut(this.ptr()) }
     }
@@ -150,30 +152,32 @@ impl<T: ?Sized + Default + Clone> Lazy<T> {
     /// Get a mutable reference to the (non-null) ref-counting pointer inside
     /// the `UnsafeCell` and `Option` (encoding the potential null), allocating
     /// and default-initializing such a ref-counting pointer if absent.
+    #[allow(clippy::mut_from_ref)]
     unsafe fn ptr(&self) -> &mut Rc<T> {
         self.raw().get_or_insert_with(|| Rc::new(T::default()))
     }
 
     /// Get a mutable reference to the optional (effectively, nullable)
     /// ref-counting pointer inside the `UnsafeCell`.
+    #[allow(clippy::mut_from_ref)]
     unsafe fn raw(&self) -> &mut Option<Rc<T>> {
         self.0.get().as_mut().unwrap()
     }
 }
 
-impl<T: ?Sized + Default + Clone> Default for Lazy<T> {
+impl<T: Default + Clone> Default for Lazy<T> {
     fn default() -> Self {
         Lazy::nil()
     }
 }
 
-impl<T: ?Sized + Default + Clone> Clone for Lazy<T> {
+impl<T: Default + Clone> Clone for Lazy<T> {
     fn clone(&self) -> Self {
         Lazy::dup(self)
     }
 }
 
-impl<T: ?Sized + Default + Clone> Deref for Lazy<T> {
+impl<T: Default + Clone> Deref for Lazy<T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
@@ -181,31 +185,31 @@ impl<T: ?Sized + Default + Clone> Deref for Lazy<T> {
     }
 }
 
-impl<T: ?Sized + Default + Clone> AsRef<T> for Lazy<T> {
+impl<T: Default + Clone> AsRef<T> for Lazy<T> {
     fn as_ref(&self) -> &T {
         Lazy::as_ref(self)
     }
 }
 
-impl<T: ?Sized + Default + Clone> AsMut<T> for Lazy<T> {
+impl<T: Default + Clone> AsMut<T> for Lazy<T> {
     fn as_mut(&mut self) -> &mut T {
         Lazy::as_mut(self)
     }
 }
 
-impl<T: ?Sized + Default + Clone> Borrow<T> for Lazy<T> {
+impl<T: Default + Clone> Borrow<T> for Lazy<T> {
     fn borrow(&self) -> &T {
         self.as_ref()
     }
 }
 
-impl<T: ?Sized + Default + Clone> From<Rc<T>> for Lazy<T> {
+impl<T: Default + Clone> From<Rc<T>> for Lazy<T> {
     fn from(ptr: Rc<T>) -> Self {
         Lazy::share(ptr)
     }
 }
 
-impl<T: ?Sized + Default + Clone> TryInto<Rc<T>> for &Lazy<T> {
+impl<T: Default + Clone> TryInto<Rc<T>> for &Lazy<T> {
     type Error = ();
 
     fn try_into(self) -> Result<Rc<T>, ()> {
@@ -215,7 +219,7 @@ impl<T: ?Sized + Default + Clone> TryInto<Rc<T>> for &Lazy<T> {
 
 /* Here we punt an assortment of standard traits through the indirection... */
 
-impl<T: ?Sized + Default + Clone + std::fmt::Debug> std::fmt::Debug for Lazy<T> {
+impl<T: Default + Clone + std::fmt::Debug> std::fmt::Debug for Lazy<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         if Lazy::is_nil(self) {
             T::default().fmt(f)
@@ -225,7 +229,7 @@ impl<T: ?Sized + Default + Clone + std::fmt::Debug> std::fmt::Debug for Lazy<T>
     }
 }
 
-impl<T: ?Sized + Default + Clone + PartialEq> PartialEq for Lazy<T> {
+impl<T: Default + Clone + PartialEq> PartialEq for Lazy<T> {
     fn eq(&self, other: &Self) -> bool {
         let default = T::default();
         let left = if Lazy::is_nil(self) {
@@ -242,9 +246,9 @@ impl<T: ?Sized + Default + Clone + PartialEq> PartialEq for Lazy<T> {
     }
 }
 
-impl<T: ?Sized + Default + Clone + Eq> Eq for Lazy<T> {}
+impl<T: Default + Clone + Eq> Eq for Lazy<T> {}
 
-impl<T: ?Sized + Default + Clone + PartialOrd> PartialOrd for Lazy<T> {
+impl<T: Default + Clone + PartialOrd> PartialOrd for Lazy<T> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         let default = T::default();
         let left = if Lazy::is_nil(self) {
@@ -261,7 +265,7 @@ impl<T: ?Sized + Default + Clone + PartialOrd> PartialOrd for Lazy<T> {
     }
 }
 
-impl<T: ?Sized + Default + Clone + Ord> Ord for Lazy<T> {
+impl<T: Default + Clone + Ord> Ord for Lazy<T> {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         let default = T::default();
         let left = if Lazy::is_nil(self) {
@@ -0,0 +1,415 @@
+//! A stylesheet parser based on the `cssparser` crate, enabled by the
+//! `library-css` feature.
+//!
+//! Like the hand-rolled parser in `css`, this one recovers from errors as CSS
+//! specifies: any rule or declaration that is malformed (or that uses syntax
+//! unsupported here) is dropped with a warning. Unlike it, this one also drops
+//! (and warns about) declarations whose values don't suit their property. The
+//! result is the same `css::Stylesheet` either way.
+//!
+//! Selectors are parsed by hand into `css::Selector` rather than with the
+//! `selectors` crate. That crate has its own selector representation, matched
+//! through its `Element` trait, so adopting it would mean replacing
+//! `css::Selector` and the matching in `style` for both parsers, not just
+//! tokenizing differently here.
+
+use crate::css::{
+    AttributeOperation, AttributeSelector, Combinator, Declaration, PseudoClass, PseudoElement,
+    Rule, Selector, SimpleSelector, Stylesheet, Unit, Value, Warning,
+};
+use crate::style::{Context, Style};
+use crate::utility::Color;
+use cssparser::{
+    AtRuleParser, BasicParseErrorKind, DeclarationListParser, ParseError, ParseErrorKind, Parser,
+    ParserInput, QualifiedRuleParser, RuleListParser, SourceLocation, ToCss, Token,
+};
+
+type Error<'i> = ParseError<'i, String>;
+
+/// Parse a CSS stylesheet, dropping (with a warning for each) any rules and
+/// declarations that can't be parsed. Warnings are in source order.
+pub fn parse(source: &str) -> (Stylesheet, Vec<Warning>) {
+    let mut input = ParserInput::new(source);
+    let mut input = Parser::new(&mut input);
+    let mut rule_list = RuleListParser::new_for_stylesheet(&mut input, RuleParser::default());
+    let mut rules = Vec::new();
+    let mut warnings = Vec::new();
+    for result in rule_list.by_ref() {
+        match result {
+            Ok(rule) => rules.push(rule),
+            Err((error, source)) => warnings.push(warning(error, source)),
+        }
+    }
+    warnings.append(&mut rule_list.parser.warnings);
+    warnings.sort_by_key(|warning| (warning.line, warning.column));
+    (Stylesheet { rules }, warnings)
+}
+
+/// Parse a list of declarations, e.g.: the value of a `style` attribute,
+/// dropping (with a warning for each) any that can't be parsed.
+pub fn parse_declarations(source: &str) -> (Vec<Declaration>, Vec<Warning>) {
+    let mut input = ParserInput::new(source);
+    let mut input = Parser::new(&mut input);
+    let mut warnings = Vec::new();
+    let declarations = parse_declaration_list(&mut input, &mut warnings);
+    (declarations, warnings)
+}
+
+/// Parse the declarations in a block, or in a whole list.
+fn parse_declaration_list(input: &mut Parser, warnings: &mut Vec<Warning>) -> Vec<Declaration> {
+    let mut declarations = Vec::new();
+    for result in DeclarationListParser::new(input, DeclarationParser) {
+        match result {
+            Ok(declaration) => declarations.push(declaration),
+            Err((error, source)) => warnings.push(warning(error, source)),
+        }
+    }
+    declarations
+}
+
+/// Describe why a rule or declaration (with the given source) was dropped.
+fn warning(error: Error, source: &str) -> Warning {
+    let reason = match error.kind {
+        ParseErrorKind::Basic(BasicParseErrorKind::UnexpectedToken(token)) => {
+            format!("unexpected `{}`", token.to_css_string())
+        }
+        ParseErrorKind::Basic(BasicParseErrorKind::EndOfInput) => {
+            String::from("unexpected end of input")
+        }
+        ParseErrorKind::Basic(BasicParseErrorKind::AtRuleInvalid(name)) => {
+            format!("unsupported at-rule `@{}`", name)
+        }
+        ParseErrorKind::Basic(BasicParseErrorKind::AtRuleBodyInvalid) => {
+            String::from("invalid at-rule body")
+        }
+        ParseErrorKind::Basic(BasicParseErrorKind::QualifiedRuleInvalid) => {
+            String::from("invalid rule")
+        }
+        ParseErrorKind::Custom(reason) => reason,
+    };
+    Warning {
+        line: error.location.line as usize + 1,
+        column: error.location.column as usize,
+        message: format!("{}; dropped `{}`", reason, source.trim()),
+    }
+}
+
+/// Create a custom parse error at the given location.
+fn error<'i>(location: SourceLocation, reason: String) -> Error<'i> {
+    location.new_custom_error(reason)
+}
+
+/// Parser for top-level rules, which collects warnings for the declarations
+/// dropped from otherwise valid rules.
+#[derive(Default)]
+struct RuleParser {
+    warnings: Vec<Warning>,
+}
+
+impl<'i> QualifiedRuleParser<'i> for RuleParser {
+    type Prelude = Vec<Selector>;
+    type QualifiedRule = Rule;
+    type Error = String;
+
+    fn parse_prelude<'t>(
+        &mut self,
+        input: &mut Parser<'i, 't>,
+    ) -> Result<Self::Prelude, Error<'i>> {
+        let mut selectors = input.parse_comma_separated(parse_selector)?;
+        // Return selectors with highest specificity first, for use in matching.
+        selectors.sort_by_key(|selector| std::cmp::Reverse(selector.specificity()));
+        Ok(selectors)
+    }
+
+    fn parse_block<'t>(
+        &mut self,
+        selectors: Self::Prelude,
+        _location: SourceLocation,
+        input: &mut Parser<'i, 't>,
+    ) -> Result<Self::QualifiedRule, Error<'i>> {
+        let declarations = parse_declaration_list(input, &mut self.warnings);
+        Ok(Rule { selectors, declarations })
+    }
+}
+
+/// At-rules are unsupported.
+impl<'i> AtRuleParser<'i> for RuleParser {
+    type PreludeNoBlock = ();
+    type PreludeBlock = ();
+    type AtRule = Rule;
+    type Error = String;
+}
+
+/// Parser for the declarations within a rule.
+struct DeclarationParser;
+
+impl<'i> cssparser::DeclarationParser<'i> for DeclarationParser {
+    type Declaration = Declaration;
+    type Error = String;
+
+    fn parse_value<'t>(
+        &mut self,
+        name: cssparser::CowRcStr<'i>,
+        input: &mut Parser<'i, 't>,
+    ) -> Result<Self::Declaration, Error<'i>> {
+        let location = input.current_source_location();
+        let mut value = Vec::new();
+        let mut important = false;
+        while !input.is_exhausted() {
+            if input.try_parse(cssparser::parse_important).is_ok() {
+                important = true;
+                input.expect_exhausted()?;
+                break;
+            }
+            value.push(parse_value(input)?);
+        }
+        let declaration = Declaration { name: name.to_ascii_lowercase(), value, important };
+        // Check the value against the property, as when styling.
+        Style::initial()
+            .try_apply_declaration(&declaration, &Style::initial(), &Context::default())
+            .map_err(|reason| error(location, reason))?;
+        Ok(declaration)
+    }
+}
+
+/// At-rules within declarations are unsupported.
+impl<'i> AtRuleParser<'i> for DeclarationParser {
+    type PreludeNoBlock = ();
+    type PreludeBlock = ();
+    type AtRule = Declaration;
+    type Error = String;
+}
+
+// Functions for parsing selectors:
+
+/// Parse one selector, i.e., simple selectors joined by combinators.
+fn parse_selector<'i, 't>(input: &mut Parser<'i, 't>) -> Result<Selector, Error<'i>> {
+    let mut simple_selectors = vec![parse_simple_selector(input)?];
+    let mut combinators = Vec::new();
+    loop {
+        // Any whitespace is a descendant combinator, unless around another.
+        let mut combinator = None;
+        loop {
+            let state = input.state();
+            let location = input.current_source_location();
+            let next = match input.next_including_whitespace() {
+                Ok(token) => token.clone(),
+                Err(_) => break,
+            };
+            let explicit = match next {
+                Token::WhiteSpace(_) => {
+                    combinator = combinator.or(Some(Combinator::Descendant));
+                    continue;
+                }
+                Token::Delim('>') => Combinator::Child,
+                Token::Delim('+') => Combinator::NextSibling,
+                Token::Delim('~') => Combinator::SubsequentSibling,
+                _ => {
+                    input.reset(&state);
+                    break;
+                }
+            };
+            if combinator.map_or(false, |combinator| combinator != Combinator::Descendant) {
+                return Err(location.new_unexpected_token_error(next));
+            }
+            combinator = Some(explicit);
+        }
+        match combinator {
+            Some(Combinator::Descendant) | None if input.is_exhausted() => break,
+            Some(combinator) => combinators.push(combinator),
+            None => return Err(input.new_error_for_next_token()),
+        }
+        simple_selectors.push(parse_simple_selector(input)?);
+    }
+    Ok(Selector::from_sequence(simple_selectors, combinators))
+}
+
+/// Parse one simple selector, e.g.: `type#id.class[attr]:first-child`
+fn parse_simple_selector<'i, 't>(input: &mut Parser<'i, 't>) -> Result<SimpleSelector, Error<'i>> {
+    let mut selector = SimpleSelector::default();
+    let mut is_empty = true;
+    loop {
+        let state = input.state();
+        let next = match input.next_including_whitespace() {
+            Ok(token) => token.clone(),
+            Err(_) => break,
+        };
+        match next {
+            Token::Ident(ref tag) => selector.tag = Some(tag.to_string()),
+            // universal selector
+            Token::Delim('*') => (),
+            Token::IDHash(ref id) => selector.id = Some(id.to_string()),
+            Token::Delim('.') => selector.class.push(input.expect_ident()?.to_string()),
+            Token::SquareBracketBlock => {
+                selector.attribute.push(input.parse_nested_block(parse_attribute_selector)?);
+            }
+            Token::Colon => parse_pseudo(input, &mut selector)?,
+            _ => {
+                input.reset(&state);
+                break;
+            }
+        }
+        is_empty = false;
+    }
+    if is_empty {
+        return Err(input.new_error_for_next_token());
+    }
+    Ok(selector)
+}
+
+/// Parse the inside of an attribute selector, e.g.: `attr^="value"`
+fn parse_attribute_selector<'i, 't>(
+    input: &mut Parser<'i, 't>,
+) -> Result<AttributeSelector, Error<'i>> {
+    let name = input.expect_ident()?.to_string();
+    let location = input.current_source_location();
+    let operation: fn(String) -> AttributeOperation = match input.next() {
+        Err(_) => return Ok(AttributeSelector { name, operation: AttributeOperation::Exists }),
+        Ok(Token::Delim('=')) => AttributeOperation::Equals,
+        Ok(Token::IncludeMatch) => AttributeOperation::Includes,
+        Ok(Token::DashMatch) => AttributeOperation::DashMatch,
+        Ok(Token::PrefixMatch) => AttributeOperation::Prefix,
+        Ok(Token::SuffixMatch) => AttributeOperation::Suffix,
+        Ok(Token::SubstringMatch) => AttributeOperation::Substring,
+        Ok(token) => {
+            let token = token.clone();
+            return Err(location.new_unexpected_token_error(token));
+        }
+    };
+    let value = input.expect_ident_or_string()?.to_string();
+    input.expect_exhausted()?;
+    Ok(AttributeSelector { name, operation: operation(value) })
+}
+
+/// Parse a pseudo-class or pseudo-element (after its first `:`) into a
+/// simple selector.
+fn parse_pseudo<'i, 't>(
+    input: &mut Parser<'i, 't>,
+    selector: &mut SimpleSelector,
+) -> Result<(), Error<'i>> {
+    let location = input.current_source_location();
+    let is_pseudo_element = input.try_parse(|input| input.expect_colon()).is_ok();
+    let (name, is_function) = match input.next_including_whitespace()? {
+        Token::Ident(name) => (name.to_ascii_lowercase(), false),
+        Token::Function(name) => (name.to_ascii_lowercase(), true),
+        token => {
+            let token = token.clone();
+            return Err(location.new_unexpected_token_error(token));
+        }
+    };
+    if is_pseudo_element || !is_function && PseudoElement::from_name(&name).is_some() {
+        // The original pseudo-elements may have a single colon.
+        selector.pseudo_element = match PseudoElement::from_name(&name) {
+            Some(pseudo_element) if !is_function => Some(pseudo_element),
+            _ => return Err(error(location, format!("unsupported pseudo-element `::{}`", name))),
+        };
+        return Ok(());
+    }
+    let pseudo_class = match &*name {
+        "nth-child" if is_function => {
+            let (a, b) = input.parse_nested_block(|input| Ok(cssparser::parse_nth(input)?))?;
+            PseudoClass::NthChild(a, b)
+        }
+        "nth-of-type" if is_function => {
+            let (a, b) = input.parse_nested_block(|input| Ok(cssparser::parse_nth(input)?))?;
+            PseudoClass::NthOfType(a, b)
+        }
+        "not" if is_function => {
+            PseudoClass::Not(Box::new(input.parse_nested_block(parse_simple_selector)?))
+        }
+        _ => match PseudoClass::from_name(&name) {
+            Some(pseudo_class) if !is_function => pseudo_class,
+            _ => return Err(error(location, format!("unsupported pseudo-class `:{}`", name))),
+        },
+    };
+    selector.pseudo_class.push(pseudo_class);
+    Ok(())
+}
+
+// Functions for parsing values:
+
+/// Parse one component value.
+fn parse_value<'i, 't>(input: &mut Parser<'i, 't>) -> Result<Value, Error<'i>> {
+    if let Ok(color) = input.try_parse(parse_color) {
+        return Ok(Value::ColorValue(color));
+    }
+    let location = input.current_source_location();
+    match input.next()?.clone() {
+        Token::Ident(keyword) => Ok(Value::Keyword(keyword.to_string())),
+        Token::Dimension { value, unit, .. } => match Unit::from_name(&unit) {
+            Some(unit) => Ok(Value::Length(value, unit)),
+            None => Err(error(location, format!("unsupported unit `{}`", unit))),
+        },
+        Token::Number { value, .. } => Ok(Value::Number(value)),
+        Token::Percentage { unit_value, .. } => Ok(Value::Percent(unit_value * 100.0)),
+        Token::QuotedString(string) => Ok(Value::StringValue(string.to_string())),
+        Token::Function(name) => {
+            let args = input.parse_nested_block(|input| input.parse_comma_separated(parse_value))?;
+            Ok(Value::Function(name.to_ascii_lowercase(), args))
+        }
+        token => Err(location.new_unexpected_token_error(token)),
+    }
+}
+
+/// Parse a color in hexadecimal or functional notation. (Named colors are
+/// left as keywords.)
+fn parse_color<'i, 't>(input: &mut Parser<'i, 't>) -> Result<Color, Error<'i>> {
+    let state = input.state();
+    match *input.next()? {
+        Token::Hash(_) | Token::IDHash(_) => (),
+        Token::Function(ref name)
+            if ["rgb", "rgba", "hsl", "hsla"].contains(&&*name.to_ascii_lowercase()) => (),
+        ref token => {
+            let token = token.clone();
+            return Err(input.new_unexpected_token_error(token));
+        }
+    }
+    input.reset(&state);
+    match cssparser::Color::parse(input)? {
+        cssparser::Color::RGBA(rgba) => {
+            Ok(Color { r: rgba.red, g: rgba.green, b: rgba.blue, a: rgba.alpha })
+        }
+        cssparser::Color::CurrentColor => Err(input.new_error_for_next_token()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_drops_unsupported_rules() {
+        let source = "p > a.b { color: red }\n@media print { p {} }\np::first-line {}";
+        let (stylesheet, warnings) = parse(source);
+        assert_eq!(stylesheet.rules.len(), 1);
+        match stylesheet.rules[0].selectors.as_slice() {
+            [Selector::Compound(subject, ancestors)] => {
+                assert_eq!(subject.tag.as_deref(), Some("a"));
+                assert_eq!(subject.class, ["b"]);
+                assert_eq!(ancestors.len(), 1);
+                assert_eq!(ancestors[0].0, Combinator::Child);
+            }
+            selectors => panic!("unexpected selectors {:?}", selectors),
+        }
+        let positions: Vec<_> = warnings.iter().map(|w| (w.line, w.column)).collect();
+        assert_eq!(positions, [(2, 7), (3, 3)]);
+    }
+
+    #[test]
+    fn parse_declarations_drops_invalid_values() {
+        let (declarations, warnings) =
+            parse_declarations("color: red; float: sideways; margin: 0 auto !important; ;");
+        let names: Vec<_> = declarations.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, ["color", "margin"]);
+        let auto = Value::Keyword(String::from("auto"));
+        assert_eq!(declarations[1].value, [Value::Number(0.0), auto]);
+        assert!(declarations[1].important);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!((warnings[0].line, warnings[0].column), (1, 19));
+
+        let source = "width: foo; font-size: small; color: #12; width: 10foo; color: @red";
+        let (declarations, warnings) = parse_declarations(source);
+        assert!(declarations.is_empty());
+        assert_eq!(warnings.len(), 5);
+    }
+}
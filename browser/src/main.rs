@@ -1,7 +1,10 @@
 //extern crate app_units;
+#[cfg(feature = "library-css")]
+extern crate cssparser;
 extern crate getopts;
 extern crate image;
 extern crate itertools;
+extern crate kuchiki;
 
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -11,6 +14,8 @@ pub mod dom;
 pub mod html;
 pub mod layout;
 pub mod lazy;
+#[cfg(feature = "library-css")]
+pub mod library_css;
 pub mod paint;
 pub mod style;
 pub mod utility;
@@ -52,11 +57,15 @@ fn command_options() -> getopts::Options {
     opts.optopt("e", "easy", "Use inputs for named easycase", "BASENAME");
     opts.optopt("d", "html", "HTML document", "FILENAME");
     opts.optopt("s", "css", "CSS stylesheet", "FILENAME");
+    opts.optopt("u", "user-css", "CSS user stylesheet", "FILENAME");
     opts.optopt("o", "out", "PNG viewport", "PATH");
     opts.optopt("", "width", "Viewport width", "PIXELS");
     opts.optopt("", "height", "Viewport height", "PIXELS");
     opts.optopt("", "scrollbar", "Scrollbar width", "PIXELS");
     opts.optopt("", "font-size", "Font size", "POINTS");
+    opts.optflag("", "lenient-html", "Recover from malformed HTML markup");
+    opts.optflag("", "spec-html", "Parse HTML with html5ever instead");
+    opts.optflag("", "dump-html", "Print parsed HTML, annotated with element indices");
     opts.optflag("", "dump-layout-tree", "Print Cassius layout tree");
     opts.optflag("v", "cassius", "Output Cassius file");
     opts.optflag("h", "help", "Print this usage summary");
@@ -99,6 +108,18 @@ fn path_to_png(args: &getopts::Matches) -> PathBuf {
     path
 }
 
+/// Parse a stylesheet (read from `path`), reporting any warnings.
+fn parse_stylesheet(path: &Path, css: String) -> css::Stylesheet {
+    #[cfg(not(feature = "library-css"))]
+    let (stylesheet, warnings) = css::parse_with_warnings(css);
+    #[cfg(feature = "library-css")]
+    let (stylesheet, warnings) = library_css::parse(&css);
+    for warning in warnings {
+        eprintln!("Warning parsing {}:{}", path.display(), warning);
+    }
+    stylesheet
+}
+
 fn layout_parameters(args: &getopts::Matches) -> layout::Parameters {
     layout::Parameters {
         viewport_width:
@@ -152,11 +173,31 @@ fn main() {
     // Read input files:
     let html = fs::read_to_string(&html_path).unwrap();
     let css = fs::read_to_string(&css_path).unwrap();
+    let user_css = args.opt_str("user-css").map(|path| {
+        let css = fs::read_to_string(&path).unwrap();
+        (PathBuf::from(path), css)
+    });
 
     // Parse, style, layout, paint and raster:
-    let document = html::parse_document(html);
-    let stylesheet = css::parse(css);
-    let style_tree = style::style_tree(&document, &stylesheet);
+    let document = if args.opt_present("spec-html") {
+        Ok(html::parse_document_spec(html))
+    } else if args.opt_present("lenient-html") {
+        html::parse_document_lenient(html)
+    } else {
+        html::parse_document(html)
+    };
+    let document = document.unwrap_or_else(|err| {
+        eprintln!("Error parsing {}:{}", html_path.display(), err);
+        std::process::exit(1)
+    });
+    if args.opt_present("dump-html") {
+        println!("{}", html::serialize_document(&document, true));
+    }
+
+    let stylesheet = parse_stylesheet(&css_path, css);
+    let user_stylesheet = user_css.map(|(path, css)| parse_stylesheet(&path, css));
+    let style_tree =
+        style::style_tree(&document, user_stylesheet.as_ref(), &stylesheet, layout_params);
     let layout_tree = layout::layout_tree(&style_tree, layout_params);
     let display_list = layout::display_list(&layout_tree);
     let canvas = paint::paint_canvas(
@@ -30,6 +30,12 @@ impl std::ops::Deref for DisplayList {
     }
 }
 
+impl Default for DisplayList {
+    fn default() -> Self {
+        DisplayList::new()
+    }
+}
+
 impl DisplayList {
     pub fn new() -> Self {
         DisplayList(Vec::new())
@@ -43,11 +49,11 @@ impl DisplayList {
         self.push_command(SolidColor(color, block));
     }
 
-    pub fn display_frame(&mut self, color: Color, frame: Edge<Rect<Pixels>>) {
-        self.push_command(SolidColor(color, frame.left));
-        self.push_command(SolidColor(color, frame.right));
-        self.push_command(SolidColor(color, frame.top));
-        self.push_command(SolidColor(color, frame.bottom));
+    pub fn display_frame(&mut self, color: Edge<Color>, frame: Edge<Rect<Pixels>>) {
+        self.push_command(SolidColor(color.left, frame.left));
+        self.push_command(SolidColor(color.right, frame.right));
+        self.push_command(SolidColor(color.top, frame.top));
+        self.push_command(SolidColor(color.bottom, frame.bottom));
     }
 }
 
@@ -68,8 +74,8 @@ impl Canvas {
         };
         Canvas {
             pixels: vec![white; width * height],
-            width: width,
-            height: height,
+            width,
+            height,
         }
     }
 
@@ -138,7 +144,7 @@ impl Canvas {
 pub fn paint_canvas(display_list: &DisplayList, width: usize, height: usize) -> Canvas {
     let mut canvas = Canvas::new(width, height);
     for command in display_list.iter() {
-        canvas.paint_command(&command);
+        canvas.paint_command(command);
     }
     canvas
 }
@@ -1,29 +1,65 @@
 //! Code for applying CSS styles to the DOM.
 //!
-//! This is not very interesting at the moment.  It will get much more
-//! complicated if I add support for compound selectors.
+//! Selectors are matched against elements in the context of their document
+//! tree, for the sake of combinators.
 
 use crate::css::{
-    self, Declaration, Rule, Selector, SimpleSelector, Specificity, Stylesheet, Value,
+    self, AttributeOperation, AttributeSelector, Combinator, Declaration, PseudoClass,
+    PseudoElement, Rule, Selector, SimpleSelector, Specificity, Stylesheet, Unit, Value,
 };
-use crate::dom::{DocumentNode, DocumentTree, ElementData};
+use crate::dom::{DocumentNode, DocumentTree, ElementData, NodeIndex};
+use crate::layout::Parameters;
 use crate::utility::{
     Automatic::{self, Auto, Given},
-    Pixels, Color, Edge,
+    Pixels, Length, Color, Edge,
 };
+use itertools::Itertools;
 use std::convert::{TryFrom, TryInto};
 
-/// Apply a stylesheet to an entire DOM tree, returning a StyledNode tree.
+/// Apply the user-agent stylesheet, any user stylesheet and an author
+/// stylesheet to an entire DOM tree, returning a StyledNode tree.
 ///
-/// This finds only the specified values at the moment. Eventually it should be extended to find the
-/// computed values too, including inherited values.
+/// Styles are computed top down, inheriting from parent to child, and with
+/// relative lengths resolved for the given output parameters.
 pub fn style_tree<'a>(
     document_tree: &'a DocumentTree,
-    stylesheet: &'a Stylesheet,
+    user_stylesheet: Option<&Stylesheet>,
+    author_stylesheet: &Stylesheet,
+    parameters: Parameters,
 ) -> StyledTree<'a> {
+    let user_agent_stylesheet = css::user_agent();
+    let mut stylesheets = vec![(Origin::UserAgent, &user_agent_stylesheet)];
+    stylesheets.extend(user_stylesheet.map(|stylesheet| (Origin::User, stylesheet)));
+    stylesheets.push((Origin::Author, author_stylesheet));
     StyledTree::new(document_tree)
-        .cascade(&css::user_agent())
-        .cascade(stylesheet)
+        .cascade(&stylesheets)
+        .compute(parameters)
+        .generate_content()
+}
+
+/// The origin of a stylesheet, in increasing order of precedence for normal
+/// (not `!important`) declarations.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Origin {
+    UserAgent,
+    User,
+    Author,
+}
+
+impl Origin {
+    /// The cascade level of declarations from this origin, from lowest to
+    /// highest precedence, as per https://www.w3.org/TR/css-cascade-3/#cascade-origin
+    /// (where important declarations reverse the order of the origins).
+    fn level(self, important: bool) -> usize {
+        match (important, self) {
+            (false, Origin::UserAgent) => 0,
+            (false, Origin::User) => 1,
+            (false, Origin::Author) => 2,
+            (true, Origin::Author) => 3,
+            (true, Origin::User) => 4,
+            (true, Origin::UserAgent) => 5,
+        }
+    }
 }
 
 /// The full styled tree, with ownership of the composite styled nodes.
@@ -38,54 +74,235 @@ impl<'a> StyledTree<'a> {
         StyledTree { document_tree, style_root }
     }
 
-    pub fn cascade(mut self, stylesheet: &Stylesheet) -> Self {
-        self.style_root.cascade(stylesheet);
+    /// Cascade the given stylesheets, each with its origin, in order.
+    pub fn cascade(mut self, stylesheets: &[(Origin, &Stylesheet)]) -> Self {
+        self.style_root.cascade(self.document_tree, stylesheets);
+        self
+    }
+
+    /// Compute styles from the cascaded declarations, top down.
+    pub fn compute(mut self, parameters: Parameters) -> Self {
+        let mut context = Context {
+            root_font_size: parameters.font_size as Pixels,
+            viewport_width: parameters.viewport_width as Pixels,
+            viewport_height: parameters.viewport_height as Pixels,
+        };
+        let root = &mut self.style_root;
+        let initial = Style { font_size: context.root_font_size, ..Style::initial() };
+        root.specified = root.compute_style(&initial, &context);
+        // Only the root's own `rem` lengths are relative to the initial font size.
+        context.root_font_size = root.specified.font_size;
+        for child in &mut root.children {
+            child.compute(&root.specified, &context);
+        }
+        self
+    }
+
+    /// Generate the contents of `::before` and `::after` pseudo-elements once
+    /// styles are cascaded, dropping any without content.
+    pub fn generate_content(mut self) -> Self {
+        self.style_root.generate_content(&mut Vec::new(), 0);
         self
     }
 }
 
 /// A node with associated style data.
+///
+/// A `::before` or `::after` pseudo-element is styled as an anonymous first
+/// or last child of its originating element, which is its `node`, as is any
+/// generated text within it.
 pub struct StyledNode<'a> {
     pub node: &'a DocumentNode,
+    /// The generated pseudo-element, if any, that this node is or is within.
+    pub pseudo_element: Option<PseudoElement>,
+    /// The text, if any, generated for a pseudo-element.
+    pub generated_text: Option<String>,
+    /// The declarations applying to this node, from lowest to highest
+    /// precedence.
+    pub declarations: Vec<Declaration>,
     pub specified: Style,
     pub children: Vec<StyledNode<'a>>,
 }
 
+/// The counters in scope during content generation, innermost last.
+type Counters = Vec<(String, i32)>;
+
 impl<'a> StyledNode<'a> {
     /// Construct the style tree, initializing style properties to their
-    /// CSS-defined defaults.
+    /// CSS-defined defaults. Each element is given `::before` and `::after`
+    /// children, to be dropped by `generate_content()` unless styled with
+    /// content.
     pub fn new(document_node: &'a DocumentNode) -> Self {
+        let mut children: Vec<_> = document_node.children.iter().map(StyledNode::new).collect();
+        if document_node.is_elem() {
+            children.insert(0, StyledNode::new_pseudo(document_node, PseudoElement::Before));
+            children.push(StyledNode::new_pseudo(document_node, PseudoElement::After));
+        }
         StyledNode {
             node: document_node,
+            pseudo_element: None,
+            generated_text: None,
+            declarations: Vec::new(),
             specified: Style::initial(),
-            children: document_node.children
-                .iter()
-                .map(StyledNode::new)
-                .collect()
+            children,
         }
     }
 
-    /// Compute style properties throughout the style tree for a given stylesheet.
-    pub fn cascade(&mut self, stylesheet: &Stylesheet) {
-        let style = &mut self.specified;
-        if let Some(elem) = self.node.as_elem() {
-            let mut rules = matching_rules(elem, stylesheet);
+    fn new_pseudo(originating_node: &'a DocumentNode, pseudo_element: PseudoElement) -> Self {
+        StyledNode {
+            node: originating_node,
+            pseudo_element: Some(pseudo_element),
+            generated_text: None,
+            declarations: Vec::new(),
+            specified: Style::initial(),
+            children: Vec::new(),
+        }
+    }
 
-            // Go through the rules from lowest to highest specificity.
-            rules.sort_by(|&(a, _), &(b, _)| a.cmp(&b));
-            for (_, rule) in rules {
-                for declaration in &rule.declarations {
-                    style.apply_declaration(declaration);
+    /// Is this node (or its ancestor) a generated pseudo-element?
+    pub fn is_generated(&self) -> bool {
+        self.pseudo_element.is_some()
+    }
+
+    /// Collect the declarations that apply throughout the style tree from the
+    /// given stylesheets (in order, each with its origin) and from `style`
+    /// attributes, sorting them by cascade level, specificity (with those of
+    /// `style` attributes highest) and then source order.
+    pub fn cascade(
+        &mut self,
+        document_tree: &DocumentTree,
+        stylesheets: &[(Origin, &Stylesheet)],
+    ) {
+        if let Some(elem) = self.node.as_elem() {
+            let (index, pseudo_element) = (self.node.index, self.pseudo_element);
+            let inline = match (pseudo_element, elem.attributes.lookup("style")) {
+                (None, Some(style)) => inline_declarations(style),
+                _ => Vec::new(),
+            };
+            let mut declarations = Vec::new();
+            for (sheet, &(origin, stylesheet)) in stylesheets.iter().enumerate() {
+                let rules = matching_rules(document_tree, index, elem, pseudo_element, stylesheet);
+                for (specificity, rule, position) in rules {
+                    for (i, declaration) in rule.declarations.iter().enumerate() {
+                        let level = origin.level(declaration.important);
+                        let source_order = (sheet, position, i);
+                        declarations.push(((level, false, specificity, source_order), declaration));
+                    }
                 }
             }
+            for (i, declaration) in inline.iter().enumerate() {
+                let level = Origin::Author.level(declaration.important);
+                let source_order = (stylesheets.len(), 0, i);
+                declarations.push(((level, true, (0, 0, 0), source_order), declaration));
+            }
+            declarations.sort_by_key(|&(precedence, _)| precedence);
+            self.declarations =
+                declarations.into_iter().map(|(_, declaration)| declaration.clone()).collect();
         }
         for child in &mut self.children {
-            child.cascade(stylesheet);
+            child.cascade(document_tree, stylesheets);
+        }
+    }
+
+    /// Compute style properties throughout this subtree, given the parent's
+    /// computed style.
+    fn compute(&mut self, parent: &Style, context: &Context) {
+        self.specified = self.compute_style(parent, context);
+        for child in &mut self.children {
+            child.compute(&self.specified, context);
+        }
+    }
+
+    /// Compute the style of this node from its declarations. The font size
+    /// is computed first, since other font-relative lengths depend on it
+    /// (whereas those in `font-size` depend on the parent's font size).
+    fn compute_style(&self, parent: &Style, context: &Context) -> Style {
+        let mut style = Style::inherit(parent);
+        // Border widths are initially `medium` (though computed as zero below
+        // unless the border has a style).
+        style.border = Edge::new(MEDIUM_BORDER_WIDTH);
+        let (font_size, other): (Vec<_>, Vec<_>) =
+            self.declarations.iter().partition(|declaration| declaration.name == "font-size");
+        // As in CSS, a declaration with an invalid value is ignored.
+        for declaration in font_size.into_iter().chain(other) {
+            style.try_apply_declaration(declaration, parent, context).ok();
         }
+        // A border has no width without a style.
+        let (border, border_style) = (style.border, style.border_style);
+        let width = |width, border_style| match border_style {
+            BorderStyle::None | BorderStyle::Hidden => 0.0,
+            _ => width,
+        };
+        style.border = Edge {
+            left: width(border.left, border_style.left),
+            right: width(border.right, border_style.right),
+            top: width(border.top, border_style.top),
+            bottom: width(border.bottom, border_style.bottom),
+        };
+        style
     }
 
     pub fn as_text(&self) -> Option<&str> {
-        self.node.as_text()
+        match self.generated_text {
+            Some(ref text) => Some(text),
+            None if self.is_generated() => None,
+            None => self.node.as_text(),
+        }
+    }
+
+    /// Generate pseudo-element contents throughout this subtree, in document
+    /// order so as to track counters. Counters instantiated by preceding
+    /// siblings are those in `counters` from `sibling_scope` on.
+    ///
+    /// https://www.w3.org/TR/css-lists-3/#auto-numbering
+    fn generate_content(&mut self, counters: &mut Counters, sibling_scope: usize) {
+        // An element that generates no box (nor do its descendants) neither
+        // changes counters nor has generated content.
+        if self.specified.display == DisplayType::None {
+            return;
+        }
+        for (name, value) in &self.specified.counter_reset {
+            let sibling_counter = counters[sibling_scope..].iter().rposition(|(n, _)| n == name);
+            match sibling_counter {
+                Some(position) => counters[sibling_scope + position].1 = *value,
+                None => counters.push((name.clone(), *value)),
+            }
+        }
+        for (name, increment) in &self.specified.counter_increment {
+            match counters.iter_mut().rev().find(|(n, _)| n == name) {
+                Some(counter) => counter.1 += increment,
+                None => counters.push((name.clone(), *increment)),
+            }
+        }
+
+        if let (Some(pseudo_element), Some(elem)) = (self.pseudo_element, self.node.as_elem()) {
+            if let Some(ref content) = self.specified.content {
+                let text: String =
+                    content.iter().map(|item| item.generate(elem, counters)).collect();
+                if !text.is_empty() {
+                    self.children.push(StyledNode {
+                        node: self.node,
+                        pseudo_element: Some(pseudo_element),
+                        generated_text: Some(text),
+                        declarations: Vec::new(),
+                        specified: Style::inherit(&self.specified),
+                        children: Vec::new(),
+                    });
+                }
+            }
+            return;
+        }
+
+        let scope = counters.len();
+        self.children.retain(|child| {
+            let style = &child.specified;
+            !child.is_generated() || style.content.is_some() && style.display != DisplayType::None
+        });
+        for child in &mut self.children {
+            child.generate_content(counters, scope);
+        }
+        // Counters instantiated by children are scoped to this node.
+        counters.truncate(scope);
     }
 }
 
@@ -100,55 +317,162 @@ pub struct Style {
     pub overflow: Overflow,
 
     // positioning offsets
-    pub left: Option<Pixels>,
-    pub right: Option<Pixels>,
-    pub top: Option<Pixels>,
-    pub bottom: Option<Pixels>,
+    pub left: Option<Length>,
+    pub right: Option<Length>,
+    pub top: Option<Length>,
+    pub bottom: Option<Length>,
 
     // font metrics
     pub font_size: Pixels,
+    pub font_style: FontStyle,
+    pub font_weight: u16,
+    pub line_height: LineHeight,
+
+    // text
+    pub color: Color,
+    pub white_space: WhiteSpace,
+    pub text_align: TextAlign,
+    pub visibility: Visibility,
 
     // box colors
     pub background_color: Color,
-    pub border_color: Color,
+    pub border_color: Edge<Color>,
 
-    // content dimensions (None ~ auto)
-    pub width: Automatic<Pixels>,
-    pub min_width: Automatic<Pixels>,
-    pub max_width: Automatic<Pixels>,
-    pub height: Automatic<Pixels>,
-    pub min_height: Automatic<Pixels>,
-    pub max_height: Automatic<Pixels>,
+    // content dimensions
+    pub box_sizing: BoxSizing,
+    pub width: Size,
+    pub min_width: Bound,
+    pub max_width: Bound,
+    pub height: Size,
+    pub min_height: Bound,
+    pub max_height: Bound,
 
     // content edge in pixels (None ~ auto)
     //pub content: Edge<Automatic<f32>>,
 
-    // margin edge in pixels (None ~ auto)
-    pub margin: Edge<Automatic<Pixels>>,
+    // margin edge (None ~ auto)
+    pub margin: Edge<Automatic<Length>>,
 
-    // padding edge in pixels
-    pub padding: Edge<Pixels>,
+    // padding edge
+    pub padding: Edge<Length>,
 
     // border edge in pixels
     pub border: Edge<Pixels>,
+    pub border_style: Edge<BorderStyle>,
+
+    // generated content (None ~ none or normal)
+    pub content: Option<Vec<ContentItem>>,
+    pub counter_reset: Vec<(String, i32)>,
+    pub counter_increment: Vec<(String, i32)>,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+/// An item of generated content.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ContentItem {
+    /// A string, e.g.: `"Note: "`
+    Text(String),
+    /// `attr(name)`
+    Attribute(String),
+    /// `counter(name)`
+    Counter(String),
+    /// `counters(name, separator)`
+    Counters(String, String),
+}
+
+impl ContentItem {
+    /// Generate the text of this item for a pseudo-element of `elem`.
+    fn generate(&self, elem: &ElementData, counters: &Counters) -> String {
+        // A counter that's not in scope is taken to be 0.
+        let values = |counter: &str| {
+            let values: Vec<_> = counters
+                .iter()
+                .filter(|(name, _)| name == counter)
+                .map(|(_, value)| value.to_string())
+                .collect();
+            if values.is_empty() { vec![0.to_string()] } else { values }
+        };
+        match *self {
+            ContentItem::Text(ref text) => text.clone(),
+            ContentItem::Attribute(ref name) => {
+                elem.attributes.lookup(name).unwrap_or("").to_string()
+            }
+            ContentItem::Counter(ref name) => values(name).pop().unwrap(),
+            ContentItem::Counters(ref name, ref separator) => values(name).join(separator),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
 pub enum DisplayType {
+    #[default]
     Inline,
     InlineBlock,
     Block,
     None,
 }
 
-impl Default for DisplayType {
-    fn default() -> Self {
-        DisplayType::Inline
-    }
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum BorderStyle {
+    #[default]
+    None,
+    Hidden,
+    Dotted,
+    Dashed,
+    Solid,
+    Double,
+    Groove,
+    Ridge,
+    Inset,
+    Outset,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum FontStyle {
+    #[default]
+    Normal,
+    Italic,
+    Oblique,
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub enum LineHeight {
+    #[default]
+    Normal,
+    // a multiple of the font size, inherited as such
+    Number(f32),
+    Length(Pixels),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum WhiteSpace {
+    #[default]
+    Normal,
+    Pre,
+    Nowrap,
+    PreWrap,
+    PreLine,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Right,
+    Center,
+    Justify,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum Visibility {
+    #[default]
+    Visible,
+    Hidden,
+    Collapse,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
 pub enum Positioned {
+    #[default]
     Static,
     Relative,
     Absolute,
@@ -156,34 +480,20 @@ pub enum Positioned {
     Sticky,
 }
 
-impl Default for Positioned {
-    fn default() -> Self {
-        Positioned::Static
-    }
-}
-
 impl Positioned {
     pub fn is_positioned(self) -> bool {
-        match self {
-            Positioned::Absolute | Positioned::Fixed => true,
-            _ => false,
-        }
+        matches!(self, Positioned::Absolute | Positioned::Fixed)
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
 pub enum Floated {
     Left,
     Right,
+    #[default]
     None,
 }
 
-impl Default for Floated {
-    fn default() -> Self {
-        Floated::None
-    }
-}
-
 impl Floated {
     pub fn is_floated(self) -> bool {
         match self {
@@ -224,16 +534,20 @@ impl Default for Clearance {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum BoxSizing {
+    #[default]
+    ContentBox,
+    BorderBox,
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
 pub enum Size {
     // {width,height}
     Length(f32), // in pixels
     Percent(f32),
-    // border-box
-    BorderBox,
-    // content-box
-    ContentBox,
     // auto
+    #[default]
     Auto,
     // fill
     Fill,
@@ -247,30 +561,13 @@ pub enum Size {
     FitContent,
 }
 
-impl Default for Size {
-    fn default() -> Self {
-        Size::Auto
-    }
-}
-
-impl TryFrom<Size> for Automatic<Pixels> {
-    type Error = Size;
-
-    fn try_from(s: Size) -> Result<Self, Self::Error> {
-        match s {
-            Size::Auto => Ok(Auto),
-            Size::Length(px) => Ok(Given(px)),
-            v => Err(v),
-        }
-    }
-}
-
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
 pub enum Bound {
     // {min,max}-{width,height}
     Length(f32), // in pixels
     Percent(f32),
     // {auto,none} = the otherwise default behavior
+    #[default]
     Auto,
     // max-content = intrinsic preferred width/height
     MaxContent,
@@ -283,12 +580,6 @@ pub enum Bound {
     FitContent,
 }
 
-impl Default for Bound {
-    fn default() -> Self {
-        Bound::Auto
-    }
-}
-
 impl std::fmt::Display for Bound {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -303,20 +594,9 @@ impl std::fmt::Display for Bound {
     }
 }
 
-impl TryFrom<Bound> for Automatic<Pixels> {
-    type Error = String;
-
-    fn try_from(s: Bound) -> Result<Self, Self::Error> {
-        match s {
-            Bound::Auto => Ok(Auto),
-            Bound::Length(px) => Ok(Given(px)),
-            v => Err(format!("recognized but unimplemented keyword `{}`", v)),
-        }
-    }
-}
-
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
 pub enum Overflow {
+    #[default]
     Visible,
     Hidden,
     Clip,
@@ -324,12 +604,6 @@ pub enum Overflow {
     Auto,
 }
 
-impl Default for Overflow {
-    fn default() -> Self {
-        Overflow::Visible
-    }
-}
-
 impl std::fmt::Display for Overflow {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -359,21 +633,35 @@ impl Style {
             bottom: None,
 
             font_size: 16.0,
+            font_style: FontStyle::default(),
+            font_weight: 400,
+            line_height: LineHeight::default(),
+
+            color: Color::rgb(0, 0, 0),
+            white_space: WhiteSpace::default(),
+            text_align: TextAlign::default(),
+            visibility: Visibility::default(),
 
             background_color: Color::default(),
-            border_color: Color::default(),
+            border_color: Edge::default(),
 
-            width: Automatic::try_from(Size::default()).unwrap(),
-            min_width: Automatic::try_from(Bound::default()).unwrap(),
-            max_width: Automatic::try_from(Bound::default()).unwrap(),
+            box_sizing: BoxSizing::default(),
+            width: Size::default(),
+            min_width: Bound::default(),
+            max_width: Bound::default(),
 
-            height: Automatic::try_from(Size::default()).unwrap(),
-            min_height: Automatic::try_from(Bound::default()).unwrap(),
-            max_height: Automatic::try_from(Bound::default()).unwrap(),
+            height: Size::default(),
+            min_height: Bound::default(),
+            max_height: Bound::default(),
 
             margin: Edge::zero(),
             padding: Edge::default(),
             border: Edge::default(),
+            border_style: Edge::default(),
+
+            content: None,
+            counter_reset: Vec::new(),
+            counter_increment: Vec::new(),
         }
     }
 
@@ -381,100 +669,541 @@ impl Style {
     /// from a parent style record, initializing the other (uninherited)
     /// properties anew, as in `initial()`.
     pub fn inherit(parent: &Self) -> Style {
-        // Apparently none of these CSS properties is inherited.
-        let mut style = Style::initial();
-        style.font_size = parent.font_size;
-        style
+        Style {
+            font_size: parent.font_size,
+            font_style: parent.font_style,
+            font_weight: parent.font_weight,
+            line_height: parent.line_height,
+            color: parent.color,
+            white_space: parent.white_space,
+            text_align: parent.text_align,
+            visibility: parent.visibility,
+            ..Style::initial()
+        }
     }
 
-    pub fn apply_declaration(&mut self, declaration: &Declaration) {
+    /// Apply a declaration, unless its value is invalid for its property.
+    /// Unsupported properties are ignored. Relative lengths are resolved at
+    /// the current font size (or, for `font-size` itself, the parent's), and
+    /// the CSS-wide keywords `inherit`, `initial` and `unset` apply to any
+    /// property.
+    pub fn try_apply_declaration(
+        &mut self,
+        declaration: &Declaration,
+        parent: &Style,
+        context: &Context,
+    ) -> Result<(), String> {
         let property = declaration.name.as_ref();
-        let value = &declaration.value;
+        let font_size = if property == "font-size" { parent.font_size } else { self.font_size };
+        let values: Vec<_> =
+            declaration.value.iter().map(|value| context.resolve(value, font_size)).collect();
+        // (As specified, borders are initially `medium`.)
+        let initial = Style { border: Edge::new(MEDIUM_BORDER_WIDTH), ..Style::initial() };
+        let from = match values.as_slice() {
+            [Value::Keyword(kw)] if kw == "inherit" => Some(parent),
+            [Value::Keyword(kw)] if kw == "unset" && is_inherited(property) => Some(parent),
+            [Value::Keyword(kw)] if kw == "initial" || kw == "unset" => Some(&initial),
+            _ => None,
+        };
+        match from {
+            Some(from) => self.copy_property(property, from),
+            None => self.apply_values(property, &values, parent)?,
+        }
+
+        if self.position.is_positioned() || self.float.is_floated() {
+            self.display = DisplayType::Block;
+        }
+        Ok(())
+    }
+
+    /// Apply the (resolved) values of a property.
+    fn apply_values(
+        &mut self,
+        property: &str,
+        values: &[Value],
+        parent: &Style,
+    ) -> Result<(), String> {
+        // Most properties take a single value.
+        let value = || match values {
+            [value] => Ok(value),
+            _ => Err(format!("expected one value but found `{}`", values.iter().join(" "))),
+        };
         match property {
-            "display" => self.display = value.try_into().expect(property),
-            "position" => self.position = value.try_into().expect(property),
-            "float" => self.float = value.try_into().expect(property),
-            "clear" => self.clear = value.try_into().expect(property),
-            "overflow" => self.overflow = value.try_into().expect(property),
-
-            "left" => self.left = Some(value.try_into().expect(property)),
-            "right" => self.right = Some(value.try_into().expect(property)),
-            "top" => self.top = Some(value.try_into().expect(property)),
-            "bottom" => self.bottom = Some(value.try_into().expect(property)),
-
-            "font-size" => self.font_size = value.try_into().expect(property),
-
-            "width" => self.width = value.try_into().expect(property),
-            "min-width" => self.min_width = value.try_into().expect(property),
-            "max-width" => self.max_width = value.try_into().expect(property),
-            "height" => self.height = value.try_into().expect(property),
-            "min-height" => self.min_height = value.try_into().expect(property),
-            "max-height" => self.max_height = value.try_into().expect(property),
-
-            "background-color" => self.background_color = value.try_into().expect(property),
-            "border-color" => self.border_color = value.try_into().expect(property),
-
-            "margin-left" => self.margin.left = value.try_into().expect(property),
-            "margin-right" => self.margin.right = value.try_into().expect(property),
-            "margin-top" => self.margin.top = value.try_into().expect(property),
-            "margin-bottom" => self.margin.bottom = value.try_into().expect(property),
-            "margin" => self.margin = Edge::new(value.try_into().expect(property)),
-
-            "padding-left" => self.padding.left = value.try_into().expect(property),
-            "padding-right" => self.padding.right = value.try_into().expect(property),
-            "padding-top" => self.padding.top = value.try_into().expect(property),
-            "padding-bottom" => self.padding.bottom = value.try_into().expect(property),
-            "padding" => self.padding = Edge::new(value.try_into().expect(property)),
-
-            "border-left-width" => self.border.left = value.try_into().expect(property),
-            "border-right-width" => self.border.right = value.try_into().expect(property),
-            "border-top-width" => self.border.top = value.try_into().expect(property),
-            "border-bottom-width" => self.border.bottom = value.try_into().expect(property),
-            "border-width" => self.border = Edge::new(value.try_into().expect(property)),
+            "display" => self.display = value()?.try_into()?,
+            "position" => self.position = value()?.try_into()?,
+            "float" => self.float = value()?.try_into()?,
+            "clear" => self.clear = value()?.try_into()?,
+            "overflow" => self.overflow = value()?.try_into()?,
+
+            "left" => self.left = Some(value()?.try_into()?),
+            "right" => self.right = Some(value()?.try_into()?),
+            "top" => self.top = Some(value()?.try_into()?),
+            "bottom" => self.bottom = Some(value()?.try_into()?),
+
+            "font-size" => self.font_size = match value()? {
+                Value::Percent(pct) => parent.font_size * pct / 100.0,
+                value => value.try_into()?,
+            },
+            "font-style" => self.font_style = value()?.try_into()?,
+            "font-weight" => self.font_weight = font_weight(value()?, parent.font_weight)?,
+            "line-height" => self.line_height = match value()? {
+                Value::Percent(pct) => LineHeight::Length(self.font_size * pct / 100.0),
+                value => value.try_into()?,
+            },
+
+            "color" => self.color = value()?.try_into()?,
+            "white-space" => self.white_space = value()?.try_into()?,
+            "text-align" => self.text_align = value()?.try_into()?,
+            "visibility" => self.visibility = value()?.try_into()?,
+
+            "box-sizing" => self.box_sizing = value()?.try_into()?,
+            "width" => self.width = value()?.try_into()?,
+            "min-width" => self.min_width = value()?.try_into()?,
+            "max-width" => self.max_width = value()?.try_into()?,
+            "height" => self.height = value()?.try_into()?,
+            "min-height" => self.min_height = value()?.try_into()?,
+            "max-height" => self.max_height = value()?.try_into()?,
+
+            "background-color" => self.background_color = value()?.try_into()?,
+            "background" => self.background_color = background_color(values)?,
+
+            "margin-left" => self.margin.left = value()?.try_into()?,
+            "margin-right" => self.margin.right = value()?.try_into()?,
+            "margin-top" => self.margin.top = value()?.try_into()?,
+            "margin-bottom" => self.margin.bottom = value()?.try_into()?,
+            "margin" => self.margin = edge(values, |value| value.try_into())?,
+
+            "padding-left" => self.padding.left = value()?.try_into()?,
+            "padding-right" => self.padding.right = value()?.try_into()?,
+            "padding-top" => self.padding.top = value()?.try_into()?,
+            "padding-bottom" => self.padding.bottom = value()?.try_into()?,
+            "padding" => self.padding = edge(values, |value| value.try_into())?,
+
+            "border-left-width" => self.border.left = border_width(value()?)?,
+            "border-right-width" => self.border.right = border_width(value()?)?,
+            "border-top-width" => self.border.top = border_width(value()?)?,
+            "border-bottom-width" => self.border.bottom = border_width(value()?)?,
+            "border-width" => self.border = edge(values, border_width)?,
+
+            "border-left-style" => self.border_style.left = value()?.try_into()?,
+            "border-right-style" => self.border_style.right = value()?.try_into()?,
+            "border-top-style" => self.border_style.top = value()?.try_into()?,
+            "border-bottom-style" => self.border_style.bottom = value()?.try_into()?,
+            "border-style" => self.border_style = edge(values, |value| value.try_into())?,
+
+            "border-left-color" => self.border_color.left = value()?.try_into()?,
+            "border-right-color" => self.border_color.right = value()?.try_into()?,
+            "border-top-color" => self.border_color.top = value()?.try_into()?,
+            "border-bottom-color" => self.border_color.bottom = value()?.try_into()?,
+            "border-color" => self.border_color = edge(values, |value| value.try_into())?,
+
+            "border" => {
+                let (width, style, color) = border(values)?;
+                self.border = Edge::new(width);
+                self.border_style = Edge::new(style);
+                self.border_color = Edge::new(color);
+            }
+            "border-left" | "border-right" | "border-top" | "border-bottom" => {
+                let (width, style, color) = border(values)?;
+                let side = &property["border-".len()..];
+                *self.border.side_mut(side).unwrap() = width;
+                *self.border_style.side_mut(side).unwrap() = style;
+                *self.border_color.side_mut(side).unwrap() = color;
+            }
+
+            "content" => self.content = match values {
+                [Value::Keyword(kw)] if kw == "none" || kw == "normal" => None,
+                [] => return Err(String::from("expected content but found nothing")),
+                _ => Some(values.iter().map(ContentItem::try_from).collect::<Result<_, _>>()?),
+            },
+            "counter-reset" => self.counter_reset = counter_changes(values, 0)?,
+            "counter-increment" => self.counter_increment = counter_changes(values, 1)?,
 
             _ => (), // XXX: Ignore any unsupported styling property!
         }
+        Ok(())
+    }
 
-        if self.position.is_positioned() || self.float.is_floated() {
-            self.display = DisplayType::Block;
+    /// Copy the value of a property (or those of a shorthand's longhands)
+    /// from another style, as for the CSS-wide keywords.
+    fn copy_property(&mut self, property: &str, from: &Style) {
+        match property {
+            "display" => self.display = from.display,
+            "position" => self.position = from.position,
+            "float" => self.float = from.float,
+            "clear" => self.clear = from.clear,
+            "overflow" => self.overflow = from.overflow,
+
+            "left" => self.left = from.left,
+            "right" => self.right = from.right,
+            "top" => self.top = from.top,
+            "bottom" => self.bottom = from.bottom,
+
+            "font-size" => self.font_size = from.font_size,
+            "font-style" => self.font_style = from.font_style,
+            "font-weight" => self.font_weight = from.font_weight,
+            "line-height" => self.line_height = from.line_height,
+
+            "color" => self.color = from.color,
+            "white-space" => self.white_space = from.white_space,
+            "text-align" => self.text_align = from.text_align,
+            "visibility" => self.visibility = from.visibility,
+
+            "box-sizing" => self.box_sizing = from.box_sizing,
+            "width" => self.width = from.width,
+            "min-width" => self.min_width = from.min_width,
+            "max-width" => self.max_width = from.max_width,
+            "height" => self.height = from.height,
+            "min-height" => self.min_height = from.min_height,
+            "max-height" => self.max_height = from.max_height,
+
+            "background-color" | "background" => self.background_color = from.background_color,
+
+            "margin" => self.margin = from.margin,
+            "padding" => self.padding = from.padding,
+            "border-width" => self.border = from.border,
+            "border-style" => self.border_style = from.border_style,
+            "border-color" => self.border_color = from.border_color,
+            "border" => {
+                self.border = from.border;
+                self.border_style = from.border_style;
+                self.border_color = from.border_color;
+            }
+
+            "content" => self.content = from.content.clone(),
+            "counter-reset" => self.counter_reset = from.counter_reset.clone(),
+            "counter-increment" => self.counter_increment = from.counter_increment.clone(),
+
+            // The sides of box properties, e.g.: `margin-left` or `border-top-width`
+            _ => match *property.split('-').collect::<Vec<_>>().as_slice() {
+                ["margin", side] => copy_side(&mut self.margin, from.margin, side),
+                ["padding", side] => copy_side(&mut self.padding, from.padding, side),
+                ["border", side] => {
+                    copy_side(&mut self.border, from.border, side);
+                    copy_side(&mut self.border_style, from.border_style, side);
+                    copy_side(&mut self.border_color, from.border_color, side);
+                }
+                ["border", side, "width"] => copy_side(&mut self.border, from.border, side),
+                ["border", side, "style"] => {
+                    copy_side(&mut self.border_style, from.border_style, side)
+                }
+                ["border", side, "color"] => {
+                    copy_side(&mut self.border_color, from.border_color, side)
+                }
+                _ => (), // XXX: Ignore any unsupported styling property!
+            },
+        }
+    }
+}
+
+/// Is a property inherited by default?
+fn is_inherited(property: &str) -> bool {
+    matches!(
+        property,
+        "font-size" | "font-style" | "font-weight" | "line-height" | "color" | "white-space"
+            | "text-align" | "visibility"
+    )
+}
+
+/// Copy one side (by name) of a box property.
+fn copy_side<T: Copy>(to: &mut Edge<T>, mut from: Edge<T>, side: &str) {
+    if let (Some(to), Some(from)) = (to.side_mut(side), from.side_mut(side)) {
+        *to = *from;
+    }
+}
+
+/// Parse a font weight, which may be relative to the parent's, as per
+/// https://www.w3.org/TR/css-fonts-3/#relative-weights
+fn font_weight(value: &Value, parent: u16) -> Result<u16, String> {
+    match value {
+        Value::Keyword(kw) => match kw.as_str() {
+            "normal" => Ok(400),
+            "bold" => Ok(700),
+            "bolder" => Ok(match parent {
+                0..=349 => 400,
+                350..=549 => 700,
+                550..=899 => 900,
+                _ => parent,
+            }),
+            "lighter" => Ok(match parent {
+                0..=99 => parent,
+                100..=549 => 100,
+                550..=749 => 400,
+                _ => 700,
+            }),
+            _ => Err(format!("invalid font weight `{}`", kw)),
+        },
+        Value::Number(n) if *n >= 1.0 && *n <= 1000.0 => Ok(*n as u16),
+        _ => Err(format!("expected font weight but found `{}`", value)),
+    }
+}
+
+/// The context in which relative lengths are resolved.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Context {
+    pub root_font_size: Pixels,
+    pub viewport_width: Pixels,
+    pub viewport_height: Pixels,
+}
+
+impl Context {
+    /// Resolve a relative length into pixels, at the given font size. Any
+    /// other value is returned as is.
+    pub fn resolve(&self, value: &Value, font_size: Pixels) -> Value {
+        let (length, unit) = match *value {
+            Value::Length(length, unit) => (length, unit),
+            _ => return value.clone(),
+        };
+        let (vw, vh) = (self.viewport_width / 100.0, self.viewport_height / 100.0);
+        let px = match unit {
+            Unit::Em => length * font_size,
+            Unit::Rem => length * self.root_font_size,
+            // Without font metrics, both the x-height and the width of "0"
+            // are assumed to be 0.5em, as per
+            // https://www.w3.org/TR/css-values-3/#font-relative-lengths
+            Unit::Ex | Unit::Ch => length * font_size / 2.0,
+            Unit::Vw => length * vw,
+            Unit::Vh => length * vh,
+            Unit::Vmin => length * vw.min(vh),
+            Unit::Vmax => length * vw.max(vh),
+            _ => return value.clone(),
+        };
+        Value::Length(px, Unit::Px)
+    }
+}
+
+/// Convert an absolute length to pixels.
+fn to_px(length: f32, unit: Unit) -> Result<Pixels, String> {
+    unit.to_px(length)
+        .ok_or_else(|| format!("unresolved relative length `{}{}`", length, unit))
+}
+
+/// Parse the value of `counter-reset` or `counter-increment` into the
+/// counters it names, each with the value following it or else the given
+/// default value.
+fn counter_changes(values: &[Value], default: i32) -> Result<Vec<(String, i32)>, String> {
+    if let [Value::Keyword(kw)] = values {
+        if kw == "none" {
+            return Ok(Vec::new());
+        }
+    }
+    let mut changes = Vec::new();
+    let mut values = values.iter().peekable();
+    while let Some(value) = values.next() {
+        let name = match value {
+            Value::Keyword(name) => name.clone(),
+            _ => return Err(format!("expected counter name but found `{}`", value)),
+        };
+        let change = match values.peek() {
+            Some(Value::Number(n)) if n.fract() == 0.0 => {
+                values.next();
+                *n as i32
+            }
+            _ => default,
+        };
+        changes.push((name, change));
+    }
+    if changes.is_empty() {
+        return Err(String::from("expected counter name but found nothing"));
+    }
+    Ok(changes)
+}
+
+/// Expand the one to four values of a box shorthand (e.g., `margin`) into
+/// its top, right, bottom and left values, as per
+/// https://www.w3.org/TR/css-box-3/#margin-shorthand
+fn edge<T: Copy>(
+    values: &[Value],
+    parse: impl Fn(&Value) -> Result<T, String>,
+) -> Result<Edge<T>, String> {
+    let values = values.iter().map(parse).collect::<Result<Vec<_>, _>>()?;
+    let (top, right, bottom, left) = match *values.as_slice() {
+        [all] => (all, all, all, all),
+        [vertical, horizontal] => (vertical, horizontal, vertical, horizontal),
+        [top, horizontal, bottom] => (top, horizontal, bottom, horizontal),
+        [top, right, bottom, left] => (top, right, bottom, left),
+        _ => return Err(format!("expected one to four values but found {}", values.len())),
+    };
+    Ok(Edge { left, right, top, bottom })
+}
+
+/// The width of a `medium` border.
+const MEDIUM_BORDER_WIDTH: Pixels = 3.0;
+
+/// Parse a border width, which may be a keyword.
+fn border_width(value: &Value) -> Result<Pixels, String> {
+    match value {
+        Value::Keyword(kw) if kw == "thin" => Ok(1.0),
+        Value::Keyword(kw) if kw == "medium" => Ok(MEDIUM_BORDER_WIDTH),
+        Value::Keyword(kw) if kw == "thick" => Ok(5.0),
+        _ => value.try_into(),
+    }
+}
+
+/// Parse the values of the `border` shorthand, or that for one side, into a
+/// width, style and color. They may be in any order, and any omitted is reset
+/// to its initial value.
+fn border(values: &[Value]) -> Result<(Pixels, BorderStyle, Color), String> {
+    let (mut width, mut style, mut color) = (None, None, None);
+    for value in values {
+        if width.is_none() && border_width(value).is_ok() {
+            width = border_width(value).ok();
+        } else if style.is_none() && BorderStyle::try_from(value).is_ok() {
+            style = BorderStyle::try_from(value).ok();
+        } else if color.is_none() && Color::try_from(value).is_ok() {
+            color = Color::try_from(value).ok();
+        } else {
+            return Err(format!("unexpected `{}` in border", value));
         }
     }
+    if values.is_empty() {
+        return Err(String::from("expected border but found nothing"));
+    }
+    Ok((
+        width.unwrap_or(MEDIUM_BORDER_WIDTH),
+        style.unwrap_or_default(),
+        color.unwrap_or_default(),
+    ))
 }
 
-/// A single CSS rule and the specificity of its most specific matching selector.
-type MatchedRule<'a> = (Specificity, &'a Rule);
+/// Find the color among the values of the `background` shorthand. Values of
+/// its other (unsupported) longhand properties are ignored.
+fn background_color(values: &[Value]) -> Result<Color, String> {
+    let mut colors = values.iter().filter_map(|value| Color::try_from(value).ok());
+    let color = colors.next().unwrap_or_default();
+    match colors.next() {
+        Some(other) => Err(format!("unexpected second background color `{}`", other)),
+        None => Ok(color),
+    }
+}
 
-/// Find all CSS rules that match the given element.
-fn matching_rules<'a>(elem: &ElementData, stylesheet: &'a Stylesheet) -> Vec<MatchedRule<'a>> {
+/// Parse the declarations in the `style` attribute of an element, reporting
+/// any warnings.
+fn inline_declarations(style: &str) -> Vec<Declaration> {
+    #[cfg(not(feature = "library-css"))]
+    let (declarations, warnings) = css::parse_declarations(style.to_string());
+    #[cfg(feature = "library-css")]
+    let (declarations, warnings) = crate::library_css::parse_declarations(style);
+    for warning in warnings {
+        eprintln!("Warning parsing style attribute `{}`:{}", style, warning);
+    }
+    declarations
+}
+
+/// A single CSS rule, the specificity of its most specific matching selector
+/// and its position in the stylesheet.
+type MatchedRule<'a> = (Specificity, &'a Rule, usize);
+
+/// Find all CSS rules that match the given element, or the given
+/// pseudo-element of it.
+fn matching_rules<'a>(
+    document_tree: &DocumentTree,
+    index: NodeIndex,
+    elem: &ElementData,
+    pseudo_element: Option<PseudoElement>,
+    stylesheet: &'a Stylesheet,
+) -> Vec<MatchedRule<'a>> {
     // For now, we just do a linear scan of all the rules.  For large
     // documents, it would be more efficient to store the rules in hash tables
     // based on tag name, id, class, etc.
     stylesheet
         .rules
         .iter()
-        .filter_map(|rule| match_rule(elem, rule))
+        .enumerate()
+        .filter_map(|(position, rule)| {
+            match_rule(document_tree, index, elem, pseudo_element, rule)
+                .map(|specificity| (specificity, rule, position))
+        })
         .collect()
 }
 
-/// If `rule` matches `elem`, return a `MatchedRule`. Otherwise return `None`.
-fn match_rule<'a>(elem: &ElementData, rule: &'a Rule) -> Option<MatchedRule<'a>> {
+/// If `rule` matches `elem` (with node index `index`) or, if given, its
+/// pseudo-element, return the specificity of its most specific matching
+/// selector. Otherwise return `None`.
+fn match_rule(
+    document_tree: &DocumentTree,
+    index: NodeIndex,
+    elem: &ElementData,
+    pseudo_element: Option<PseudoElement>,
+    rule: &Rule,
+) -> Option<Specificity> {
     // Find the first (most specific) matching selector.
     rule.selectors
         .iter()
-        .find(|selector| matches(elem, *selector))
-        .map(|selector| (selector.specificity(), rule))
+        .filter(|selector| selector.pseudo_element() == pseudo_element)
+        .find(|selector| matches(document_tree, index, elem, selector))
+        .map(Selector::specificity)
 }
 
 /// Selector matching:
-fn matches(elem: &ElementData, selector: &Selector) -> bool {
+fn matches(
+    document_tree: &DocumentTree,
+    index: NodeIndex,
+    elem: &ElementData,
+    selector: &Selector,
+) -> bool {
     match *selector {
-        Selector::Simple(ref simple_selector) => matches_simple_selector(elem, simple_selector),
+        Selector::Simple(ref simple_selector) => {
+            matches_simple_selector(document_tree, index, elem, simple_selector)
+        }
+        Selector::Compound(ref subject, ref context) => {
+            matches_simple_selector(document_tree, index, elem, subject)
+                && matches_context(document_tree, index, context)
+        }
     }
 }
 
-fn matches_simple_selector(elem: &ElementData, selector: &SimpleSelector) -> bool {
-    // Check type selector
-    if selector.tag.iter().any(|name| elem.tag != *name) {
+/// Does the element with node index `index` have the context required by the
+/// remaining (right-to-left) combinators and simple selectors of a compound
+/// selector? Descendant and subsequent-sibling combinators backtrack, trying
+/// each candidate element in turn.
+fn matches_context(
+    document_tree: &DocumentTree,
+    index: NodeIndex,
+    context: &[(Combinator, SimpleSelector)],
+) -> bool {
+    let ((combinator, selector), rest) = match context.split_first() {
+        Some(first_and_rest) => first_and_rest,
+        None => return true,
+    };
+    let mut prev_siblings = element_siblings(document_tree, index, DocumentTree::prev_sibling);
+    let matches_candidate = |candidate: NodeIndex| {
+        element(document_tree, candidate).map_or(false, |elem| {
+            matches_simple_selector(document_tree, candidate, elem, selector)
+        }) && matches_context(document_tree, candidate, rest)
+    };
+    match combinator {
+        Combinator::Descendant => document_tree.ancestors(index).any(matches_candidate),
+        Combinator::Child => document_tree.parent(index).map_or(false, matches_candidate),
+        Combinator::NextSibling => prev_siblings.take(1).any(matches_candidate),
+        Combinator::SubsequentSibling => prev_siblings.any(matches_candidate),
+    }
+}
+
+/// Lookup the element with the given node index, if it is one.
+fn element(document_tree: &DocumentTree, index: NodeIndex) -> Option<&ElementData> {
+    document_tree.node(index).and_then(DocumentNode::as_elem)
+}
+
+/// Iterate over the element siblings of a node in one direction, given by
+/// `DocumentTree::prev_sibling` or `DocumentTree::next_sibling`.
+fn element_siblings<'a>(
+    document_tree: &'a DocumentTree,
+    index: NodeIndex,
+    step: fn(&DocumentTree, NodeIndex) -> Option<NodeIndex>,
+) -> impl Iterator<Item = NodeIndex> + 'a {
+    std::iter::successors(step(document_tree, index), move |&sibling| step(document_tree, sibling))
+        .filter(move |&sibling| element(document_tree, sibling).is_some())
+}
+
+fn matches_simple_selector(
+    document_tree: &DocumentTree,
+    index: NodeIndex,
+    elem: &ElementData,
+    selector: &SimpleSelector,
+) -> bool {
+    // Check type selector (case-insensitively, as for an HTML document)
+    if selector.tag.iter().any(|name| !elem.tag.eq_ignore_ascii_case(name)) {
         return false;
     }
 
@@ -493,10 +1222,116 @@ fn matches_simple_selector(elem: &ElementData, selector: &SimpleSelector) -> boo
         return false;
     }
 
+    // Check attribute selectors
+    if selector
+        .attribute
+        .iter()
+        .any(|attribute| !matches_attribute_selector(elem, attribute))
+    {
+        return false;
+    }
+
+    // Check pseudo-classes
+    if selector
+        .pseudo_class
+        .iter()
+        .any(|pseudo_class| !matches_pseudo_class(document_tree, index, elem, pseudo_class))
+    {
+        return false;
+    }
+
     // We didn't find any non-matching selector components.
     true
 }
 
+fn matches_attribute_selector(elem: &ElementData, selector: &AttributeSelector) -> bool {
+    use AttributeOperation::*;
+
+    let value = match elem.attributes.lookup(&selector.name) {
+        Some(value) => value,
+        None => return false,
+    };
+    // Selectors for an empty (or, for `~=`, whitespace-containing) substring
+    // never match, as per https://www.w3.org/TR/selectors-3/#attribute-substrings
+    match selector.operation {
+        Exists => true,
+        Equals(ref expected) => value == expected,
+        Includes(ref word) => {
+            !word.is_empty()
+                && !word.contains(char::is_whitespace)
+                && value.split_whitespace().any(|item| item == word)
+        }
+        DashMatch(ref prefix) => {
+            value == prefix
+                || value.starts_with(prefix.as_str()) && value[prefix.len()..].starts_with('-')
+        }
+        Prefix(ref prefix) => !prefix.is_empty() && value.starts_with(prefix.as_str()),
+        Suffix(ref suffix) => !suffix.is_empty() && value.ends_with(suffix.as_str()),
+        Substring(ref substring) => !substring.is_empty() && value.contains(substring.as_str()),
+    }
+}
+
+fn matches_pseudo_class(
+    document_tree: &DocumentTree,
+    index: NodeIndex,
+    elem: &ElementData,
+    pseudo_class: &PseudoClass,
+) -> bool {
+    use PseudoClass::*;
+
+    let is_root = document_tree.parent(index).is_none();
+    let mut prev_siblings = element_siblings(document_tree, index, DocumentTree::prev_sibling);
+    let mut next_siblings = element_siblings(document_tree, index, DocumentTree::next_sibling);
+    match *pseudo_class {
+        // Only elements with parents count as children.
+        FirstChild => !is_root && prev_siblings.next().is_none(),
+        LastChild => !is_root && next_siblings.next().is_none(),
+        OnlyChild => !is_root && prev_siblings.next().is_none() && next_siblings.next().is_none(),
+        NthChild(a, b) => !is_root && matches_nth(a, b, prev_siblings.count() + 1),
+        NthOfType(a, b) => {
+            let same_type = prev_siblings.filter(|&sibling| {
+                element(document_tree, sibling).map_or(false, |sibling| sibling.tag == elem.tag)
+            });
+            !is_root && matches_nth(a, b, same_type.count() + 1)
+        }
+        Empty => document_tree.node(index).map_or(false, |node| node.children.is_empty()),
+        Root => is_root,
+        Not(ref argument) => !matches_simple_selector(document_tree, index, elem, argument),
+    }
+}
+
+/// Is the (1-based) `position` of the form `an+b`, for some `n >= 0`?
+fn matches_nth(a: i32, b: i32, position: usize) -> bool {
+    let offset = position as i32 - b;
+    if a == 0 {
+        offset == 0
+    } else {
+        offset % a == 0 && offset / a >= 0
+    }
+}
+
+impl TryFrom<&Value> for ContentItem {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::StringValue(s) => Ok(ContentItem::Text(s.clone())),
+            Value::Function(name, args) => match (name.as_str(), args.as_slice()) {
+                ("attr", [Value::Keyword(attr)]) => Ok(ContentItem::Attribute(attr.clone())),
+                // Any counter style is ignored, as if decimal.
+                ("counter", [Value::Keyword(counter), ..]) => {
+                    Ok(ContentItem::Counter(counter.clone()))
+                }
+                ("counters", [Value::Keyword(counter), Value::StringValue(separator), ..]) => {
+                    Ok(ContentItem::Counters(counter.clone(), separator.clone()))
+                }
+                _ => Err(format!("invalid content `{}`", v)),
+            },
+            _ => Err(format!("expected content but found `{}`", v)),
+        }
+    }
+}
+
 impl TryFrom<&Value> for DisplayType {
     type Error = String;
 
@@ -514,6 +1349,29 @@ impl TryFrom<&Value> for DisplayType {
     }
 }
 
+impl TryFrom<&Value> for BorderStyle {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Keyword(kw) => match kw.as_str() {
+                "none" => Ok(BorderStyle::None),
+                "hidden" => Ok(BorderStyle::Hidden),
+                "dotted" => Ok(BorderStyle::Dotted),
+                "dashed" => Ok(BorderStyle::Dashed),
+                "solid" => Ok(BorderStyle::Solid),
+                "double" => Ok(BorderStyle::Double),
+                "groove" => Ok(BorderStyle::Groove),
+                "ridge" => Ok(BorderStyle::Ridge),
+                "inset" => Ok(BorderStyle::Inset),
+                "outset" => Ok(BorderStyle::Outset),
+                _ => Err(format!("invalid border style `{}`", kw)),
+            },
+            _ => Err(format!("expected border style but found `{}`", v)),
+        }
+    }
+}
+
 impl TryFrom<&Value> for Positioned {
     type Error = String;
 
@@ -548,14 +1406,27 @@ impl TryFrom<&Value> for Floated {
     }
 }
 
+impl TryFrom<&Value> for BoxSizing {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Keyword(kw) => match kw.as_str() {
+                "content-box" => Ok(BoxSizing::ContentBox),
+                "border-box" => Ok(BoxSizing::BorderBox),
+                _ => Err(format!("invalid box sizing `{}`", kw)),
+            },
+            _ => Err(format!("expected box sizing but found `{}`", v)),
+        }
+    }
+}
+
 impl TryFrom<&Value> for Size {
     type Error = String;
 
     fn try_from(v: &Value) -> Result<Self, Self::Error> {
         match v {
             Value::Keyword(kw) => match kw.as_str() {
-                "border-box" => Ok(Size::BorderBox),
-                "content-box" => Ok(Size::ContentBox),
                 "auto" => Ok(Size::Auto),
                 "fill" => Ok(Size::Fill),
                 "max-content" => Ok(Size::MaxContent),
@@ -564,7 +1435,8 @@ impl TryFrom<&Value> for Size {
                 "fit-content" => Ok(Size::FitContent),
                 _ => Err(format!("invalid dimension size `{}`", kw)),
             },
-            Value::Length(len, unit) => Ok(Size::Length(unit.to_px(*len))),
+            Value::Length(len, unit) => Ok(Size::Length(to_px(*len, *unit)?)),
+            Value::Number(n) if *n == 0.0 => Ok(Size::Length(0.0)),
             Value::Percent(pct) => Ok(Size::Percent(*pct)),
             _ => Err(format!("expected dimension size but found `{}`", v)),
         }
@@ -584,7 +1456,8 @@ impl TryFrom<&Value> for Bound {
                 "fit-content" => Ok(Bound::FitContent),
                 _ => Err(format!("invalid dimension bound `{}`", kw)),
             },
-            Value::Length(len, unit) => Ok(Bound::Length(unit.to_px(*len))),
+            Value::Length(len, unit) => Ok(Bound::Length(to_px(*len, *unit)?)),
+            Value::Number(n) if *n == 0.0 => Ok(Bound::Length(0.0)),
             Value::Percent(pct) => Ok(Bound::Percent(*pct)),
             _ => Err(format!("expected dimension bound but found `{}`", v)),
         }
@@ -608,6 +1481,87 @@ impl TryFrom<&Value> for Clearance {
     }
 }
 
+impl TryFrom<&Value> for FontStyle {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Keyword(kw) => match kw.as_str() {
+                "normal" => Ok(FontStyle::Normal),
+                "italic" => Ok(FontStyle::Italic),
+                "oblique" => Ok(FontStyle::Oblique),
+                _ => Err(format!("invalid font style `{}`", kw)),
+            },
+            _ => Err(format!("expected font style but found `{}`", v)),
+        }
+    }
+}
+
+impl TryFrom<&Value> for LineHeight {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Keyword(kw) if kw == "normal" => Ok(LineHeight::Normal),
+            Value::Number(n) => Ok(LineHeight::Number(*n)),
+            Value::Length(len, unit) => Ok(LineHeight::Length(to_px(*len, *unit)?)),
+            _ => Err(format!("expected line height but found `{}`", v)),
+        }
+    }
+}
+
+impl TryFrom<&Value> for WhiteSpace {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Keyword(kw) => match kw.as_str() {
+                "normal" => Ok(WhiteSpace::Normal),
+                "pre" => Ok(WhiteSpace::Pre),
+                "nowrap" => Ok(WhiteSpace::Nowrap),
+                "pre-wrap" => Ok(WhiteSpace::PreWrap),
+                "pre-line" => Ok(WhiteSpace::PreLine),
+                _ => Err(format!("invalid white space mode `{}`", kw)),
+            },
+            _ => Err(format!("expected white space mode but found `{}`", v)),
+        }
+    }
+}
+
+impl TryFrom<&Value> for TextAlign {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Keyword(kw) => match kw.as_str() {
+                // Text is assumed to be left-to-right.
+                "left" | "start" => Ok(TextAlign::Left),
+                "right" | "end" => Ok(TextAlign::Right),
+                "center" => Ok(TextAlign::Center),
+                "justify" => Ok(TextAlign::Justify),
+                _ => Err(format!("invalid text alignment `{}`", kw)),
+            },
+            _ => Err(format!("expected text alignment but found `{}`", v)),
+        }
+    }
+}
+
+impl TryFrom<&Value> for Visibility {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Keyword(kw) => match kw.as_str() {
+                "visible" => Ok(Visibility::Visible),
+                "hidden" => Ok(Visibility::Hidden),
+                "collapse" => Ok(Visibility::Collapse),
+                _ => Err(format!("invalid visibility `{}`", kw)),
+            },
+            _ => Err(format!("expected visibility but found `{}`", v)),
+        }
+    }
+}
+
 impl TryFrom<&Value> for Overflow {
     type Error = String;
 
@@ -626,14 +1580,29 @@ impl TryFrom<&Value> for Overflow {
     }
 }
 
-impl TryFrom<&Value> for Automatic<Pixels> {
+impl TryFrom<&Value> for Automatic<Length> {
     type Error = String;
 
     fn try_from(v: &Value) -> Result<Self, Self::Error> {
         match v {
-            Value::Length(len, unit) => Ok(Given(unit.to_px(*len))),
+            Value::Length(len, unit) => Ok(Given(Length::Px(to_px(*len, *unit)?))),
+            Value::Number(n) if *n == 0.0 => Ok(Given(Length::Px(0.0))),
+            Value::Percent(pct) => Ok(Given(Length::Percent(*pct))),
             Value::Keyword(kw) if kw == "auto" => Ok(Auto),
-            _ => Err(format!("expected auto/length but found `{}`", v)),
+            _ => Err(format!("expected auto/length/percentage but found `{}`", v)),
+        }
+    }
+}
+
+impl TryFrom<&Value> for Length {
+    type Error = String;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match v {
+            Value::Length(len, unit) => Ok(Length::Px(to_px(*len, *unit)?)),
+            Value::Number(n) if *n == 0.0 => Ok(Length::Px(0.0)),
+            Value::Percent(pct) => Ok(Length::Percent(*pct)),
+            _ => Err(format!("expected length/percentage but found `{}`", v)),
         }
     }
 }
@@ -643,7 +1612,8 @@ impl TryFrom<&Value> for Pixels {
 
     fn try_from(v: &Value) -> Result<Self, Self::Error> {
         match v {
-            Value::Length(len, unit) => Ok(unit.to_px(*len)),
+            Value::Length(len, unit) => to_px(*len, *unit),
+            Value::Number(n) if *n == 0.0 => Ok(0.0),
             _ => Err(format!("expected length but found `{}`", v)),
         }
     }
@@ -662,3 +1632,243 @@ impl TryFrom<&Value> for Color {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dom::ElementData;
+    use crate::html;
+
+    const PARAMETERS: Parameters =
+        Parameters { viewport_width: 1280, viewport_height: 720, scrollbar_width: 0, font_size: 16 };
+
+    /// Find the styled node of the element with the given ID.
+    fn find<'a, 'b>(node: &'b StyledNode<'a>, id: &str) -> Option<&'b StyledNode<'a>> {
+        let elem = node.node.as_elem();
+        if node.pseudo_element.is_none() && elem.and_then(ElementData::id) == Some(id) {
+            return Some(node);
+        }
+        node.children.iter().find_map(|child| find(child, id))
+    }
+
+    /// The background color of the element with ID `a`, once the given
+    /// stylesheet is cascaded.
+    fn background_color(source: &str, css: &str) -> Color {
+        style_of(source, css).background_color
+    }
+
+    /// The computed style of the element with ID `a`, given a stylesheet.
+    fn style_of(source: &str, css: &str) -> Style {
+        cascaded_style(source, None, css)
+    }
+
+    /// The computed style of the element with ID `a`, once the given user (if
+    /// any) and author stylesheets are cascaded.
+    fn cascaded_style(source: &str, user_css: Option<&str>, author_css: &str) -> Style {
+        let document = html::parse_document(String::from(source)).unwrap();
+        let user_stylesheet = user_css.map(|css| css::parse(String::from(css)));
+        let author_stylesheet = css::parse(String::from(author_css));
+        let styled =
+            style_tree(&document, user_stylesheet.as_ref(), &author_stylesheet, PARAMETERS);
+        find(&styled.style_root, "a").expect("a").specified.clone()
+    }
+
+    /// Collect the text generated throughout a subtree, in document order.
+    fn generated_text(node: &StyledNode, text: &mut Vec<String>) {
+        text.extend(node.generated_text.clone());
+        for child in &node.children {
+            generated_text(child, text);
+        }
+    }
+
+    #[test]
+    fn attribute_selectors() {
+        let source = "<html><p id=a lang=en-US class=x title='a b c' data-v=abcdef>x</p></html>";
+        let (red, blue) = (Color::rgb(255, 0, 0), Color::rgb(0, 0, 255));
+        // An attribute selector outweighs a type selector.
+        let selected = |selector: &str| {
+            let css =
+                format!("{} {{ background-color: red; }} p {{ background-color: blue; }}", selector);
+            background_color(source, &css)
+        };
+        for selector in &[
+            "[lang]", "[LANG]", "[lang=en-US]", "[title~=b]", "[lang|=en]", "[lang|=en-US]",
+            "[data-v^=abc]", "[data-v$=\"def\"]", "[data-v*='cde']", "p[class][lang]",
+        ] {
+            assert_eq!(selected(selector), red, "{}", selector);
+        }
+        for selector in &[
+            "[href]", "[lang=en]", "[title~='a b']", "[lang|=e]", "[data-v^=bc]", "[data-v$=de]",
+            "[data-v*=ace]", "[title~='']", "[data-v^='']",
+        ] {
+            assert_eq!(selected(selector), blue, "{}", selector);
+        }
+
+        let specificity = |selector: &str| {
+            let stylesheet = css::parse(format!("{} {{}}", selector));
+            stylesheet.rules[0].selectors[0].specificity()
+        };
+        assert_eq!(specificity("[a]"), (0, 1, 0));
+        assert_eq!(specificity("p[a=b][c~=d]"), (0, 2, 1));
+        assert_eq!(specificity("#x.y[z|=w]"), (1, 2, 0));
+    }
+
+    #[test]
+    fn matches_nth_positions() {
+        let positions = |a, b| (1..=7).filter(|&i| matches_nth(a, b, i)).collect::<Vec<_>>();
+        assert_eq!(positions(2, 1), [1, 3, 5, 7]);
+        assert_eq!(positions(2, 0), [2, 4, 6]);
+        assert_eq!(positions(0, 3), [3]);
+        assert_eq!(positions(3, -1), [2, 5]);
+        assert_eq!(positions(-1, 3), [1, 2, 3]);
+        assert_eq!(positions(-2, 0), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn counters_skip_undisplayed_elements() {
+        let source = "<html><p>a</p><p class=n>b</p><p>c</p></html>";
+        let document = html::parse_document(String::from(source)).unwrap();
+        let css = "p { counter-increment: n; } p::before { content: counter(n); } \
+                   .n { display: none; }";
+        let stylesheet = css::parse(String::from(css));
+        let mut text = Vec::new();
+        generated_text(&style_tree(&document, None, &stylesheet, PARAMETERS).style_root, &mut text);
+        assert_eq!(text, ["1", "2"]);
+    }
+
+    #[test]
+    fn resolve_relative_lengths() {
+        let context =
+            Context { root_font_size: 20.0, viewport_width: 1000.0, viewport_height: 500.0 };
+        let resolve = |value| match context.resolve(&value, 10.0) {
+            Value::Length(px, Unit::Px) => px,
+            value => panic!("unresolved {}", value),
+        };
+        assert_eq!(resolve(Value::Length(2.0, Unit::Em)), 20.0);
+        assert_eq!(resolve(Value::Length(2.0, Unit::Rem)), 40.0);
+        assert_eq!(resolve(Value::Length(2.0, Unit::Ex)), 10.0);
+        assert_eq!(resolve(Value::Length(2.0, Unit::Ch)), 10.0);
+        assert_eq!(resolve(Value::Length(10.0, Unit::Vw)), 100.0);
+        assert_eq!(resolve(Value::Length(10.0, Unit::Vh)), 50.0);
+        assert_eq!(resolve(Value::Length(10.0, Unit::Vmin)), 50.0);
+        assert_eq!(resolve(Value::Length(10.0, Unit::Vmax)), 100.0);
+        // Absolute lengths and percentages are left for later.
+        let inch = Value::Length(1.0, Unit::In);
+        assert_eq!(context.resolve(&inch, 10.0), inch);
+        assert_eq!(context.resolve(&Value::Percent(50.0), 10.0), Value::Percent(50.0));
+    }
+
+    #[test]
+    fn font_relative_lengths_in_context() {
+        let source = "<html><div><p id=a>x</p></div></html>";
+        // The font size is relative to the parent's, other lengths to its own.
+        let css = "div { font-size: 20px; } p { font-size: 2em; width: 2em; padding-left: 1rem; }";
+        let style = style_of(source, css);
+        assert_eq!(style.font_size, 40.0);
+        assert_eq!(style.width, Size::Length(80.0));
+        assert_eq!(style.padding.left, Length::Px(16.0));
+        let style = style_of(source, "div { font-size: 20px; } p { width: 1em; font-size: 0.5em; }");
+        assert_eq!(style.font_size, 10.0);
+        assert_eq!(style.width, Size::Length(10.0));
+        let style = style_of(source, "p { font-size: 1.5rem; height: 10vh; max-width: 50vmin; }");
+        assert_eq!(style.font_size, 24.0);
+        assert_eq!(style.height, Size::Length(72.0));
+        assert_eq!(style.max_width, Bound::Length(360.0));
+        // A percentage font size is also relative to the parent's.
+        let style = style_of(source, "div { font-size: 20px; } p { font-size: 150%; width: 1em; }");
+        assert_eq!(style.font_size, 30.0);
+        assert_eq!(style.width, Size::Length(30.0));
+    }
+
+    #[test]
+    fn edge_expands_one_to_four_values() {
+        let number = |value: &Value| match *value {
+            Value::Number(n) => Ok(n),
+            _ => Err(String::from("expected a number")),
+        };
+        let expand = |values: &[f32]| {
+            let values: Vec<_> = values.iter().map(|&n| Value::Number(n)).collect();
+            edge(&values, number).map(|e| (e.top, e.right, e.bottom, e.left))
+        };
+        assert_eq!(expand(&[1.0]), Ok((1.0, 1.0, 1.0, 1.0)));
+        assert_eq!(expand(&[1.0, 2.0]), Ok((1.0, 2.0, 1.0, 2.0)));
+        assert_eq!(expand(&[1.0, 2.0, 3.0]), Ok((1.0, 2.0, 3.0, 2.0)));
+        assert_eq!(expand(&[1.0, 2.0, 3.0, 4.0]), Ok((1.0, 2.0, 3.0, 4.0)));
+        assert!(expand(&[]).is_err());
+        assert!(expand(&[1.0; 5]).is_err());
+    }
+
+    #[test]
+    fn cascade_order() {
+        let source = "<html><p id=a class=b>x</p></html>";
+        let (red, blue) = (Color::rgb(255, 0, 0), Color::rgb(0, 0, 255));
+        let color = |user_css, author_css| {
+            cascaded_style(source, user_css, author_css).background_color
+        };
+        // Specificity, then source order.
+        assert_eq!(color(None, "#a { background-color: red } p.b { background-color: blue }"), red);
+        assert_eq!(color(None, ".b { background-color: red } .b { background-color: blue }"), blue);
+        assert_eq!(color(None, ".b { background-color: red; background-color: blue }"), blue);
+        // Importance outweighs specificity.
+        let author_css = "p { background-color: red !important } #a { background-color: blue }";
+        assert_eq!(color(None, author_css), red);
+        // Author declarations override user ones, unless important.
+        let user_css = "#a { background-color: red }";
+        assert_eq!(color(Some(user_css), "p { background-color: blue }"), blue);
+        let user_css = "p { background-color: red !important }";
+        assert_eq!(color(Some(user_css), "p { background-color: blue !important }"), red);
+    }
+
+    #[test]
+    fn inline_style_precedence() {
+        let (red, blue) = (Color::rgb(255, 0, 0), Color::rgb(0, 0, 255));
+        let source = "<html><p id=a style=\"background-color: red\">x</p></html>";
+        assert_eq!(background_color(source, "#a { background-color: blue; }"), red);
+        assert_eq!(background_color(source, "p { background-color: blue !important; }"), blue);
+        let source =
+            "<html><p id=a style=\"background-color: red !important; ; color\">x</p></html>";
+        assert_eq!(background_color(source, "#a { background-color: blue !important; }"), red);
+    }
+
+    #[test]
+    fn invalid_values_are_ignored() {
+        let valid = "width: 10px; font-size: 20px; background-color: red";
+        let invalid = "width: foo; font-size: small; background-color: #12; width: 10foo; \
+                       background-color: @red";
+        let check = |style: Style| {
+            assert_eq!(style.width, Size::Length(10.0));
+            assert_eq!(style.font_size, 20.0);
+            assert_eq!(style.background_color, Color::rgb(255, 0, 0));
+        };
+        // In a stylesheet, and in a `style` attribute.
+        check(style_of("<html><p id=a>x</p></html>", &format!("p {{ {}; {} }}", valid, invalid)));
+        let source = format!("<html><p id=a style='{}; {}'>x</p></html>", valid, invalid);
+        check(style_of(&source, ""));
+    }
+
+    #[test]
+    fn css_wide_keywords() {
+        let source = "<html><div style='color: red; margin-left: 10px; border: 2px solid'>\
+                      <p id=a>x</p></div></html>";
+        let (red, black) = (Color::rgb(255, 0, 0), Color::rgb(0, 0, 0));
+        // `color` is inherited...
+        assert_eq!(style_of(source, "p { color: blue; } #a { color: inherit; }").color, red);
+        assert_eq!(style_of(source, "p { color: blue; } #a { color: unset; }").color, red);
+        assert_eq!(style_of(source, "#a { color: initial; }").color, black);
+        // ...whereas `margin-left` and `border` aren't.
+        let margin_left = |css| style_of(source, css).margin.left;
+        assert_eq!(margin_left("#a { margin-left: inherit; }"), Given(Length::Px(10.0)));
+        let zero = Given(Length::Px(0.0));
+        assert_eq!(margin_left("p { margin-left: 5px; } #a { margin-left: unset; }"), zero);
+        assert_eq!(margin_left("p { margin: 5px; } #a { margin-left: initial; }"), zero);
+        let style = style_of(source, "#a { border: inherit; }");
+        assert_eq!((style.border.top, style.border_style.top), (2.0, BorderStyle::Solid));
+        let style = style_of(source, "#a { border: 1px dotted; border-top: unset; }");
+        assert_eq!((style.border.top, style.border_style.top), (0.0, BorderStyle::None));
+        assert_eq!((style.border.left, style.border_style.left), (1.0, BorderStyle::Dotted));
+        // An initial border width is `medium`, once it has a style.
+        let css = "#a { border-width: 1px; border-width: initial; border-style: solid; }";
+        let style = style_of(source, css);
+        assert_eq!(style.border.top, MEDIUM_BORDER_WIDTH);
+    }
+}
@@ -6,7 +6,7 @@ pub trait Additive: Copy + ops::Add<Self, Output = Self> + ops::Sub<Self, Output
 
 impl<T: Copy + ops::Add<Self, Output = Self> + ops::Sub<Self, Output = Self>> Additive for T {}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -20,7 +20,7 @@ impl Color {
             r: red,
             g: green,
             b: blue,
-            a: 0,
+            a: 255,
         }
     }
 
@@ -33,6 +33,37 @@ impl Color {
         }
     }
 
+    /// Convert from hue (in degrees), saturation, lightness and alpha (each
+    /// clamped to between 0 and 1), as per
+    /// https://www.w3.org/TR/css-color-3/#hsl-color
+    pub fn hsla(hue: f32, saturation: f32, lightness: f32, alpha: f32) -> Color {
+        let clamp = |x: f32| x.clamp(0.0, 1.0);
+        let (saturation, lightness) = (clamp(saturation), clamp(lightness));
+        let high = if lightness <= 0.5 {
+            lightness * (saturation + 1.0)
+        } else {
+            lightness + saturation - lightness * saturation
+        };
+        let low = lightness * 2.0 - high;
+        // Each channel is piecewise linear in the hue, in sixths of a turn.
+        let sixths = hue.rem_euclid(360.0) / 60.0;
+        let channel = |offset: f32| {
+            let h = (sixths + offset).rem_euclid(6.0);
+            let level = if h < 1.0 {
+                low + (high - low) * h
+            } else if h < 3.0 {
+                high
+            } else if h < 4.0 {
+                low + (high - low) * (4.0 - h)
+            } else {
+                low
+            };
+            (level * 255.0).round() as u8
+        };
+        let alpha = (clamp(alpha) * 255.0).round() as u8;
+        Color::rgba(channel(2.0), channel(0.0), channel(-2.0), alpha)
+    }
+
     pub fn by_css_name(name: &str) -> Option<Color> {
         let rgb = |r, g, b| Some(Color::rgb(r, g, b));
         let rgba = |r, g, b, a| Some(Color::rgba(r, g, b, a));
@@ -235,17 +266,6 @@ impl Color {
     }
 }
 
-impl Default for Color {
-    fn default() -> Self {
-        Color {
-            r: 0,
-            g: 0,
-            b: 0,
-            a: 0,
-        }
-    }
-}
-
 impl fmt::Display for Color {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -262,8 +282,38 @@ impl fmt::Display for Color {
 /// A length or co-ordinate measured in literal pixels.
 pub type Pixels = f32;
 
-pub const MIN_PIXELS: Pixels = std::f32::MIN;
-pub const MAX_PIXELS: Pixels = std::f32::MAX;
+pub const MIN_PIXELS: Pixels = f32::MIN;
+pub const MAX_PIXELS: Pixels = f32::MAX;
+
+/// A length in pixels, or as a percentage of some reference length (e.g.,
+/// the width of the containing block) that is only known during layout.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Length {
+    Px(Pixels),
+    Percent(f32),
+}
+
+impl Length {
+    /// Resolve the length in pixels, given the reference length.
+    pub fn resolve(&self, reference: Pixels) -> Pixels {
+        match self {
+            Length::Px(px) => *px,
+            Length::Percent(pct) => reference * pct / 100.0,
+        }
+    }
+}
+
+impl Default for Length {
+    fn default() -> Self {
+        Length::Px(0.0)
+    }
+}
+
+impl From<Pixels> for Length {
+    fn from(px: Pixels) -> Self {
+        Length::Px(px)
+    }
+}
 
 /// A rectangular co-ordinate point.
 #[derive(Clone, Copy, Default, PartialEq, Debug)]
@@ -291,9 +341,18 @@ impl<T: Copy> Edge<T> {
             bottom: breadth,
         }
     }
-}
 
-impl<T: Additive> Edge<T> {
+    /// Lookup a side by its name, e.g.: `"top"`
+    pub fn side_mut(&mut self, name: &str) -> Option<&mut T> {
+        match name {
+            "left" => Some(&mut self.left),
+            "right" => Some(&mut self.right),
+            "top" => Some(&mut self.top),
+            "bottom" => Some(&mut self.bottom),
+            _ => None,
+        }
+    }
+
     pub fn transform<U, F: Fn(T) -> U>(&self, tr: F) -> Edge<U> {
         Edge {
             left: tr(self.left),
@@ -304,7 +363,7 @@ impl<T: Additive> Edge<T> {
     }
 }
 
-impl Edge<Automatic<Pixels>> {
+impl Edge<Automatic<Length>> {
     pub fn auto() -> Self {
         Edge {
             left: Auto,
@@ -315,7 +374,7 @@ impl Edge<Automatic<Pixels>> {
     }
 
     pub fn zero() -> Self {
-        let zero = Given(0.0);
+        let zero = Given(Length::Px(0.0));
         Edge {
             left: zero,
             right: zero,
@@ -473,6 +532,18 @@ impl<V: Default + Copy> Automatic<V> {
     }
 }
 
+impl Automatic<Length> {
+    /// Resolve a given length in pixels, given the reference length.
+    pub fn resolve(&self, reference: Pixels) -> Automatic<Pixels> {
+        match self {
+            Auto => Auto,
+            Given(length) => Given(length.resolve(reference)),
+        }
+    }
+}
+
+// Deriving would needlessly require `V: Default`.
+#[allow(clippy::derivable_impls)]
 impl<V> Default for Automatic<V> {
     fn default() -> Self {
         Auto
@@ -734,7 +805,7 @@ impl FloatCursor {
     }
 
     pub fn inline_space(&self, inline_start: Pixels, inline_end: Pixels, block_start: Pixels) -> (Pixels, Pixels) {
-        if let Some(level) = self.inline.iter().skip_while(|l| l.bottom < block_start).next() {
+        if let Some(level) = self.inline.iter().find(|l| l.bottom >= block_start) {
             // println!("branch1");
             (inline_start.max(level.left), inline_end.min(level.right))
         } else {
@@ -769,3 +840,33 @@ pub enum Block {
     InlineRoot, BlockFlow, BlockRoot, Floated, Replaced
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn length_resolve() {
+        assert_eq!(Length::Px(12.0).resolve(400.0), 12.0);
+        assert_eq!(Length::Percent(25.0).resolve(400.0), 100.0);
+        assert_eq!(Length::Percent(50.0).resolve(0.0), 0.0);
+    }
+
+    #[test]
+    fn automatic_length_resolve() {
+        assert_eq!(Automatic::<Length>::Auto.resolve(400.0), Auto);
+        assert_eq!(Given(Length::Percent(10.0)).resolve(400.0), Given(40.0));
+        assert_eq!(Given(Length::Px(3.0)).resolve(400.0), Given(3.0));
+    }
+
+    #[test]
+    fn color_hsla() {
+        assert_eq!(Color::hsla(0.0, 1.0, 0.5, 1.0), Color::rgb(255, 0, 0));
+        assert_eq!(Color::hsla(120.0, 1.0, 0.25, 1.0), Color::rgb(0, 128, 0));
+        assert_eq!(Color::hsla(-120.0, 1.0, 0.5, 0.5), Color::rgba(0, 0, 255, 128));
+        assert_eq!(Color::hsla(30.0, 0.5, 0.5, 1.0), Color::rgb(191, 128, 64));
+        // Saturation, lightness and alpha are clamped.
+        assert_eq!(Color::hsla(200.0, 2.0, 1.5, -1.0), Color::rgba(255, 255, 255, 0));
+        assert_eq!(Color::hsla(200.0, -1.0, 0.5, 1.0), Color::rgb(128, 128, 128));
+    }
+}